@@ -0,0 +1,2462 @@
+//! USB device enumeration behind the cursed-usb TUI, usable on its own.
+//!
+//! ```no_run
+//! for device in cursed_usb::enumerate() {
+//!     println!("{} {} {}", device.id(), device.name, device.display_path());
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, LazyLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// One-shot listing with the default backend, usb.ids names and DFU rules
+pub fn enumerate() -> Vec<UsbDevice> {
+    get_usb_devices(
+        Backend::detect(),
+        &load_usb_ids(),
+        &DfuRules::default(),
+        &LsusbOptions::default(),
+    )
+    .devices
+}
+
+/// A USB device as seen by one poll
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UsbDevice {
+    pub bus: String,
+    pub device: String,
+    pub vendor_id: String,
+    pub product_id: String,
+    pub name: String,
+    pub vendor_name: Option<String>,  // Resolved from usb.ids
+    pub product_name: Option<String>, // Resolved from usb.ids
+    pub manufacturer: Option<String>, // iManufacturer string from the device
+    pub serial: Option<String>,       // iSerialNumber string from the device
+    pub fw_version: Option<String>,   // bcdDevice as "1.02"
+    pub usb_version: Option<String>,  // bcdUSB, the spec version the device claims, as "3.20"
+    pub max_packet_size0: Option<u8>, // Raw bMaxPacketSize0; see ep0_max_packet()
+    pub is_dfu: bool,
+    pub speed: Option<UsbSpeed>,
+    pub drivers: Vec<String>, // Kernel drivers bound to the device's interfaces
+    pub class_code: Option<u8>, // bDeviceClass, or the first interface's class when 0x00
+    pub subclass_code: Option<u8>, // Matching bDeviceSubClass / bInterfaceSubClass
+    pub interface_classes: Vec<u8>, // bInterfaceClass of each interface, in interface order
+    pub max_power_ma: Option<u16>, // bMaxPower of the active configuration
+    pub port_path: Vec<u8>,   // Hub ports from the root hub down, empty for root hubs
+    pub endpoint_count: usize, // Endpoints across all interfaces, excluding ep0
+    pub endpoint_kinds: Vec<String>, // "IN bulk", "OUT interrupt", ... in interface order
+    pub urb_count: Option<u64>, // `urbnum`: URBs submitted so far, a rough traffic counter
+    pub authorized: Option<bool>, // `authorized`: false once the kernel or usbguard cut it off
+    pub runtime_status: Option<String>, // `power/runtime_status`: "active", "suspended", ...
+    // Attributes that exist but couldn't be read, e.g. "serial: Permission
+    // denied (os error 13)", so a missing value isn't mistaken for an absent one
+    pub diagnostics: Vec<String>,
+    pub dev_path: String,           // /dev/bus/usb/BUS/DEVICE or tty path
+    pub tty_paths: Vec<String>,     // /dev/ttyUSB0, /dev/ttyACM0, etc.; a dual-port probe has two
+    pub sysfs_dir: Option<PathBuf>, // Where the sysfs backend found it; None from lsusb and rusb
+}
+
+/// Version of the JSON export format, bumped whenever a device field is
+/// removed or renamed. New fields don't bump it; readers should ignore
+/// fields they don't know.
+///
+/// 1. `{ "schema_version", "generated_at", "devices": [...] }`, with each
+///    device's ttys in `tty_paths`
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The envelope around exported devices, so consumers can tell formats apart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export<T> {
+    pub schema_version: u32,
+    /// RFC 3339 timestamp of the poll
+    pub generated_at: String,
+    pub devices: Vec<T>,
+}
+
+impl<T> Export<T> {
+    /// Wrap devices in the current `SCHEMA_VERSION`
+    pub fn new(generated_at: String, devices: Vec<T>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generated_at,
+            devices,
+        }
+    }
+}
+
+/// Communications Device Class (CDC), covering ACM modems and serial ports
+const CDC_CLASS: u8 = 0x02;
+
+/// Mass Storage Class: USB sticks, card readers, external drives
+const MASS_STORAGE_CLASS: u8 = 0x08;
+
+/// Vendors of USB-serial bridge chips that the kernel exposes as a tty
+const USB_SERIAL_VENDORS: [&str; 4] = [
+    "0403", // FTDI
+    "10c4", // Silicon Labs (CP210x)
+    "1a86", // WCH (CH340/CH341)
+    "067b", // Prolific (PL2303)
+];
+
+/// Negotiated link speed, as reported in sysfs `speed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsbSpeed {
+    Low,        // 1.5 Mbps
+    Full,       // 12 Mbps
+    High,       // 480 Mbps
+    Super,      // 5 Gbps
+    SuperPlus,  // 10 Gbps
+    SuperPlus2, // 20 Gbps (Gen 2x2)
+}
+
+impl UsbSpeed {
+    pub fn from_sysfs(value: &str) -> Option<Self> {
+        match value.trim() {
+            "1.5" => Some(UsbSpeed::Low),
+            "12" => Some(UsbSpeed::Full),
+            "480" => Some(UsbSpeed::High),
+            "5000" => Some(UsbSpeed::Super),
+            "10000" => Some(UsbSpeed::SuperPlus),
+            "20000" => Some(UsbSpeed::SuperPlus2),
+            _ => None,
+        }
+    }
+
+    pub fn mbps(&self) -> &'static str {
+        match self {
+            UsbSpeed::Low => "1.5",
+            UsbSpeed::Full => "12",
+            UsbSpeed::High => "480",
+            UsbSpeed::Super => "5000",
+            UsbSpeed::SuperPlus => "10000",
+            UsbSpeed::SuperPlus2 => "20000",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UsbSpeed::Low => "Low",
+            UsbSpeed::Full => "Full",
+            UsbSpeed::High => "High",
+            UsbSpeed::Super => "Super",
+            UsbSpeed::SuperPlus => "Super+",
+            UsbSpeed::SuperPlus2 => "Super+ 2x2",
+        }
+    }
+}
+
+impl UsbDevice {
+    /// Unique key for this specific device (bus + device number, serial as tiebreaker)
+    pub fn key(&self) -> String {
+        match &self.serial {
+            Some(serial) => format!("{}:{}:{}", self.bus, self.device, serial),
+            None => format!("{}:{}", self.bus, self.device),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        format!("{}:{}", self.vendor_id, self.product_id)
+    }
+
+    /// The ID in decimal, as some vendor tools quote it ("1155:57105").
+    /// A half that isn't valid hex shows as "?".
+    pub fn decimal_id(&self) -> String {
+        let decimal = |hex: &str| {
+            u16::from_str_radix(hex, 16).map_or_else(|_| "?".to_string(), |id| id.to_string())
+        };
+        format!("{}:{}", decimal(&self.vendor_id), decimal(&self.product_id))
+    }
+
+    /// Identity that survives re-enumeration. The device number changes on
+    /// every reconnect, so flapping can't be tracked by `key()`.
+    pub fn identity(&self) -> String {
+        match &self.serial {
+            Some(serial) => format!("{}:{}", self.id(), serial),
+            None => self.id(),
+        }
+    }
+
+    /// Key for flap detection: the identity, pinned to the port when there's
+    /// no serial so two identical serial-less boards don't count as one
+    pub fn flap_key(&self) -> String {
+        match &self.serial {
+            Some(_) => self.identity(),
+            None => format!("{}@{}", self.id(), self.port_path_string()),
+        }
+    }
+
+    /// The device's sysfs directory: where the sysfs backend found it, else a
+    /// scan of /sys/bus/usb/devices. Too slow for every frame; fine for one action.
+    pub fn locate_sysfs_dir(&self) -> Option<PathBuf> {
+        self.sysfs_dir
+            .clone()
+            .or_else(|| find_sysfs_dir(&self.bus, &self.device))
+    }
+
+    /// Topology in sysfs notation, e.g. "1-3.2.1", or just the bus for a root hub
+    pub fn port_path_string(&self) -> String {
+        let bus = self.bus.parse::<u32>().unwrap_or(0);
+        if self.port_path.is_empty() {
+            return bus.to_string();
+        }
+        let ports: Vec<String> = self.port_path.iter().map(|port| port.to_string()).collect();
+        format!("{}-{}", bus, ports.join("."))
+    }
+
+    /// Topology of the hub this device is plugged into; None for root hubs
+    pub fn parent_port_path_string(&self) -> Option<String> {
+        let (_, parent) = self.port_path.split_last()?;
+        let bus = self.bus.parse::<u32>().unwrap_or(0);
+        if parent.is_empty() {
+            return Some(bus.to_string());
+        }
+        let ports: Vec<String> = parent.iter().map(|port| port.to_string()).collect();
+        Some(format!("{}-{}", bus, ports.join(".")))
+    }
+
+    /// Whether this device should have a serial port: a CDC device or
+    /// interface, or a known USB-serial bridge chip
+    pub fn expects_tty(&self) -> bool {
+        self.class_code == Some(CDC_CLASS)
+            || self.interface_classes.contains(&CDC_CLASS)
+            || USB_SERIAL_VENDORS.contains(&self.vendor_id.as_str())
+    }
+
+    /// Why a present device might still not work: deauthorized or runtime-suspended
+    pub fn state_problem(&self) -> Option<&str> {
+        if self.authorized == Some(false) {
+            return Some("deauthorized");
+        }
+        self.runtime_status
+            .as_deref()
+            .filter(|status| *status == "suspended")
+    }
+
+    /// Whether this is a USB stick, card reader or other mass storage device
+    pub fn is_mass_storage(&self) -> bool {
+        self.class_code == Some(MASS_STORAGE_CLASS)
+            || self.interface_classes.contains(&MASS_STORAGE_CLASS)
+    }
+
+    /// Whether this device sits at or below the hub at `prefix` ("1", "1-3", "1-3.2")
+    pub fn is_under_port(&self, prefix: &str) -> bool {
+        let path = self.port_path_string();
+        match path.strip_prefix(prefix) {
+            Some(rest) => {
+                rest.is_empty() || rest.starts_with(['-', '.']) || prefix.ends_with(['-', '.'])
+            }
+            None => false,
+        }
+    }
+
+    /// The first tty, the one to use when only one will do
+    pub fn tty_path(&self) -> Option<&str> {
+        self.tty_paths.first().map(String::as_str)
+    }
+
+    /// A USB 3 device running at USB 2 speed or below, which usually means
+    /// a USB 2 cable, hub or port somewhere in the chain
+    pub fn fell_back_from_usb3(&self) -> bool {
+        let claims_usb3 = self
+            .usb_version
+            .as_deref()
+            .and_then(|version| version.split('.').next()?.parse::<u32>().ok())
+            .is_some_and(|major| major >= 3);
+        claims_usb3
+            && matches!(
+                self.speed,
+                Some(UsbSpeed::Low | UsbSpeed::Full | UsbSpeed::High)
+            )
+    }
+
+    /// Endpoint 0's max packet size in bytes. SuperSpeed descriptors store
+    /// it as a power of two, so 9 there means 512.
+    pub fn ep0_max_packet(&self) -> Option<u16> {
+        let raw = self.max_packet_size0?;
+        let superspeed = matches!(
+            self.speed,
+            Some(UsbSpeed::Super | UsbSpeed::SuperPlus | UsbSpeed::SuperPlus2)
+        );
+        if superspeed && raw < 16 {
+            Some(1 << raw)
+        } else {
+            Some(u16::from(raw))
+        }
+    }
+
+    /// Display path - prefer tty over bus path
+    pub fn display_path(&self) -> &str {
+        self.tty_path().unwrap_or(&self.dev_path)
+    }
+}
+
+/// (bus, devnum) -> every tty a USB device provides, in port order
+pub type TtyMap = HashMap<(u32, u32), Vec<String>>;
+
+/// Build a map of (bus, devnum) -> tty device paths by scanning /dev/serial/by-id
+/// and /sys/class/tty. This is fast because it just reads symlinks
+pub fn get_tty_map() -> TtyMap {
+    get_tty_map_with_warnings(&mut vec![])
+}
+
+/// `get_tty_map`, noting ttys that exist but couldn't be traced to a USB device
+fn get_tty_map_with_warnings(warnings: &mut Vec<String>) -> TtyMap {
+    let mut map = TtyMap::new();
+    let add = |map: &mut TtyMap, key, tty_name: &str| {
+        let ttys = map.entry(key).or_default();
+        let path = format!("/dev/{}", tty_name);
+        if !ttys.contains(&path) {
+            ttys.push(path);
+        }
+    };
+
+    // Method 1: Check /dev/serial/by-id (fastest, has nice names)
+    if let Ok(entries) = fs::read_dir("/dev/serial/by-id") {
+        for entry in entries.flatten() {
+            if let Ok(target) = fs::read_link(entry.path()) {
+                let target_str = target.to_string_lossy();
+                // Extract ttyUSB0, ttyACM0, ... from the target
+                if let Some(tty_name) = target_str.strip_prefix("../../") {
+                    // Now find which bus/dev this corresponds to
+                    match get_tty_bus_dev(tty_name) {
+                        Ok(Some(key)) => add(&mut map, key, tty_name),
+                        Ok(None) => {}
+                        Err(warning) => warnings.push(warning),
+                    }
+                }
+            }
+        }
+    }
+
+    // Method 2: Every tty in /sys/class/tty that sits on USB, whatever its
+    // name (ttyUSB, ttyACM, ttyAMA, ...) or index
+    let mut tty_names: Vec<String> = fs::read_dir("/sys/class/tty")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| is_usb_tty(name))
+        .collect();
+    tty_names.sort_by_key(|name| tty_sort_key(name));
+    for tty_name in tty_names {
+        match get_tty_bus_dev(&tty_name) {
+            Ok(Some(key)) => add(&mut map, key, &tty_name),
+            Ok(None) => {}
+            // Already reported by the by-id pass
+            Err(warning) if warnings.contains(&warning) => {}
+            Err(warning) => warnings.push(warning),
+        }
+    }
+    for ttys in map.values_mut() {
+        ttys.sort_by_key(|path| tty_sort_key(path));
+    }
+
+    map
+}
+
+/// ttyUSB2 before ttyUSB10, so a multi-port adapter lists its ports in order
+fn tty_sort_key(path: &str) -> (String, u32) {
+    let prefix = path.trim_end_matches(|c: char| c.is_ascii_digit());
+    let index = path[prefix.len()..].parse().unwrap_or(0);
+    (prefix.to_string(), index)
+}
+
+/// Whether a tty's device hangs off a USB host controller (.../usb1/1-2/...),
+/// ruling out on-board UARTs and virtual consoles before looking any closer
+fn is_usb_tty(tty_name: &str) -> bool {
+    fs::canonicalize(format!("/sys/class/tty/{}/device", tty_name)).is_ok_and(|path| {
+        path.components().any(|component| {
+            let component = component.as_os_str().to_string_lossy();
+            component
+                .strip_prefix("usb")
+                .is_some_and(|bus| !bus.is_empty() && bus.chars().all(|c| c.is_ascii_digit()))
+        })
+    })
+}
+
+/// Get bus and device number for a tty device by reading sysfs. Ok(None)
+/// when there's no such tty; Err describes a tty that couldn't be traced.
+fn get_tty_bus_dev(tty_name: &str) -> Result<Option<(u32, u32)>, String> {
+    // Read /sys/class/tty/ttyUSB0/device/../.. to find the USB device
+    let device_path = format!("/sys/class/tty/{}/device", tty_name);
+    if !Path::new(&device_path).exists() {
+        return Ok(None);
+    }
+
+    // Follow symlinks to find the USB device directory
+    let real_path = fs::canonicalize(&device_path)
+        .map_err(|err| format!("canonicalize failed for {}: {}", tty_name, err))?;
+
+    // Walk up to find busnum/devnum
+    let read_number = |path: &Path, attr: &str| -> Result<u32, String> {
+        fs::read_to_string(path.join(attr))
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| format!("couldn't read {} for {}", attr, tty_name))
+    };
+    let mut current = real_path.as_path();
+    for _ in 0..5 {
+        let Some(parent) = current.parent() else {
+            break;
+        };
+        current = parent;
+        if current.join("busnum").exists() && current.join("devnum").exists() {
+            let bus = read_number(current, "busnum")?;
+            let dev = read_number(current, "devnum")?;
+            return Ok(Some((bus, dev)));
+        }
+    }
+
+    Err(format!("no USB device above {}", tty_name))
+}
+
+/// How DFU/bootloader devices are recognised beyond the interface descriptor
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DfuRules {
+    /// Case-insensitive name substrings that suggest a bootloader
+    pub dfu_keywords: Vec<String>,
+    /// Known bootloader VID:PID pairs, e.g. "0483:df11"
+    pub dfu_ids: Vec<String>,
+}
+
+impl Default for DfuRules {
+    fn default() -> Self {
+        Self {
+            dfu_keywords: vec!["dfu".into(), "download".into(), "boot".into()],
+            dfu_ids: vec![],
+        }
+    }
+}
+
+impl DfuRules {
+    pub fn matches_name(&self, name: &str) -> bool {
+        let name_lower = name.to_lowercase();
+        self.dfu_keywords
+            .iter()
+            .any(|keyword| name_lower.contains(&keyword.to_lowercase()))
+    }
+
+    pub fn matches_id(&self, id: &str) -> bool {
+        self.dfu_ids
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(id))
+    }
+}
+
+/// Vendor ID -> (vendor name, product ID -> product name), all IDs lowercase hex
+pub type UsbIds = HashMap<String, (String, HashMap<String, String>)>;
+
+const USB_IDS_PATHS: &[&str] = &[
+    "/var/lib/usbutils/usb.ids",
+    "/usr/share/misc/usb.ids",
+    "/usr/share/hwdata/usb.ids",
+];
+
+/// Load the first usb.ids database found. Missing database gives an empty map.
+pub fn load_usb_ids() -> UsbIds {
+    USB_IDS_PATHS
+        .iter()
+        .find_map(|path| fs::read(path).ok())
+        .map(|bytes| parse_usb_ids(&String::from_utf8_lossy(&bytes)))
+        .unwrap_or_default()
+}
+
+fn parse_usb_ids(contents: &str) -> UsbIds {
+    let mut ids = UsbIds::new();
+    let mut current_vendor: Option<String> = None;
+
+    for line in contents.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix('\t') {
+            // Interface lines are double-tabbed, we only want products
+            if rest.starts_with('\t') {
+                continue;
+            }
+            let (Some(vendor), Some((pid, name))) = (&current_vendor, split_id_line(rest)) else {
+                continue;
+            };
+            if let Some((_, products)) = ids.get_mut(vendor) {
+                products.insert(pid, name);
+            }
+        } else if let Some((vid, name)) = split_id_line(line) {
+            ids.insert(vid.clone(), (name, HashMap::new()));
+            current_vendor = Some(vid);
+        } else {
+            // Class/language/etc. sections follow the vendor list
+            current_vendor = None;
+        }
+    }
+
+    ids
+}
+
+/// Split "0483  STMicroelectronics" into ("0483", "STMicroelectronics")
+fn split_id_line(line: &str) -> Option<(String, String)> {
+    let (id, name) = line.split_once("  ")?;
+    if id.len() != 4 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((id.to_lowercase(), name.trim().to_string()))
+}
+
+/// Fill in vendor/product names from the database, keeping the existing name if unresolved
+fn resolve_names(device: &mut UsbDevice, ids: &UsbIds) {
+    let Some((vendor, products)) = ids.get(&device.vendor_id.to_lowercase()) else {
+        return;
+    };
+    device.vendor_name = Some(vendor.clone());
+    device.product_name = products.get(&device.product_id.to_lowercase()).cloned();
+
+    if device.name == "Unknown" {
+        device.name = match &device.product_name {
+            Some(product) => format!("{} {}", vendor, product),
+            None => vendor.clone(),
+        };
+    }
+}
+
+const SYSFS_USB_DEVICES: &str = "/sys/bus/usb/devices";
+
+/// Where device information comes from, chosen once at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Read /sys/bus/usb/devices directly (no subprocess)
+    Sysfs,
+    /// Shell out to `lsusb` and parse its output
+    Lsusb,
+    /// Ask libusb, reading descriptors and strings from the devices themselves;
+    /// no subprocess and no sysfs, so not tied to Linux
+    #[cfg(feature = "rusb")]
+    Rusb,
+}
+
+impl Backend {
+    /// Prefer sysfs, fall back to lsusb when it isn't mounted
+    pub fn detect() -> Self {
+        if Path::new(SYSFS_USB_DEVICES).is_dir() {
+            Backend::Sysfs
+        } else {
+            Backend::Lsusb
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Sysfs => "sysfs",
+            Backend::Lsusb => "lsusb",
+            #[cfg(feature = "rusb")]
+            Backend::Rusb => "rusb",
+        }
+    }
+
+    /// The backend called `name`, or why this build can't use it
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "sysfs" => Ok(Backend::Sysfs),
+            "lsusb" => Ok(Backend::Lsusb),
+            #[cfg(feature = "rusb")]
+            "rusb" => Ok(Backend::Rusb),
+            #[cfg(not(feature = "rusb"))]
+            "rusb" => Err("this build has no rusb backend; rebuild with `--features rusb`".into()),
+            _ => Err(format!(
+                "unknown backend {:?}; expected sysfs, lsusb or rusb",
+                name
+            )),
+        }
+    }
+}
+
+/// How to invoke `lsusb`: which binary, and whether to go through `sudo -n`,
+/// or a captured listing to replay instead
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LsusbOptions {
+    /// Binary to run instead of whatever `lsusb` is on PATH
+    pub path: Option<PathBuf>,
+    /// Run it as `sudo -n lsusb`, which fails instead of prompting for a password
+    pub sudo: bool,
+    /// Saved `lsusb` output to parse instead of running anything; forces the
+    /// lsusb backend
+    pub file: Option<PathBuf>,
+    /// Leave `tty_paths` empty rather than scan /dev/serial and /sys/class/tty.
+    /// With `file`, a poll no longer depends on the machine it runs on.
+    pub skip_tty_scan: bool,
+}
+
+impl LsusbOptions {
+    /// Run lsusb with `args` (or read the captured listing) and return its
+    /// stdout, or why it couldn't run
+    pub fn run(&self, args: &[String]) -> Result<String, String> {
+        if let Some(file) = &self.file {
+            if !args.is_empty() {
+                return Err(format!(
+                    "{} is a captured listing; it has no `lsusb {}` output",
+                    file.display(),
+                    args.join(" ")
+                ));
+            }
+            return fs::read_to_string(file)
+                .map_err(|err| format!("couldn't read {}: {}", file.display(), err));
+        }
+        let program = self.path.as_deref().unwrap_or(Path::new("lsusb"));
+        let mut command = if self.sudo {
+            let mut command = Command::new("sudo");
+            command.arg("-n").arg(program);
+            command
+        } else {
+            Command::new(program)
+        };
+        // Never let anything sit waiting on the TUI's stdin
+        let output = command
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("couldn't run {}: {}", program.display(), err))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        if self.sudo && !output.status.success() && stdout.trim().is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("password is required") {
+                return Err(format!(
+                    "sudo wants a password for {}; run `sudo -v` first or allow it with NOPASSWD",
+                    program.display()
+                ));
+            }
+            return Err(format!(
+                "sudo -n {} failed: {}",
+                program.display(),
+                stderr.trim()
+            ));
+        }
+        Ok(stdout)
+    }
+}
+
+/// Result of one poll
+#[derive(Debug, Clone, Default)]
+pub struct Enumeration {
+    pub devices: Vec<UsbDevice>,
+    /// `lsusb` lines that didn't look like a device, so nothing vanishes silently
+    pub parse_failures: usize,
+    /// Non-fatal problems hit along the way, e.g. "couldn't read busnum for ttyUSB3"
+    pub warnings: Vec<String>,
+}
+
+pub fn get_usb_devices(
+    backend: Backend,
+    ids: &UsbIds,
+    rules: &DfuRules,
+    lsusb: &LsusbOptions,
+) -> Enumeration {
+    let started = Instant::now();
+    let mut warnings = vec![];
+    let tty_map = if lsusb.skip_tty_scan {
+        TtyMap::new()
+    } else {
+        get_tty_map_with_warnings(&mut warnings)
+    };
+    let backend = if lsusb.file.is_some() {
+        Backend::Lsusb
+    } else {
+        backend
+    };
+
+    let (mut devices, parse_failures) = match backend {
+        Backend::Sysfs => (enumerate_from_sysfs(&tty_map, rules, &mut warnings), 0),
+        Backend::Lsusb => enumerate_from_lsusb(lsusb, &tty_map, rules, &mut warnings),
+        #[cfg(feature = "rusb")]
+        Backend::Rusb => (enumerate_from_rusb(&tty_map, rules, &mut warnings), 0),
+    };
+    for device in &mut devices {
+        resolve_names(device, ids);
+        if rules.matches_id(&device.id()) {
+            device.is_dfu = true;
+        }
+    }
+    tracing::debug!(
+        backend = backend.name(),
+        duration_ms = started.elapsed().as_secs_f64() * 1000.0,
+        devices = devices.len(),
+        parse_failures,
+        warnings = warnings.len(),
+        "scan_completed"
+    );
+    Enumeration {
+        devices,
+        parse_failures,
+        warnings,
+    }
+}
+
+/// One background poll: the result, how long the scan took, and when it was
+/// asked for: the `refresh()` call behind it, or the scan start on a timer
+pub type PollResult = (Enumeration, Duration, Instant);
+
+enum PollMessage {
+    /// Sent by `refresh()` at this instant
+    Refresh(Instant),
+    Stop,
+}
+
+/// Shortest gap between two scans, however fast refreshes are triggered;
+/// holding 'r' shouldn't fork lsusb at key-repeat rate
+pub const MIN_SCAN_GAP: Duration = Duration::from_millis(100);
+
+/// Scans in a row with the same devices, and no new traffic on any of them,
+/// before the poller starts backing off
+pub const IDLE_SCANS_BEFORE_BACKOFF: u32 = 10;
+
+/// Ceiling for the backed-off interval, unless the configured one is longer
+pub const MAX_IDLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Interval after one more unchanged scan: half as long again once the
+/// set has been stable for a while, up to the ceiling
+fn backed_off(current: Duration, base: Duration, unchanged: u32) -> Duration {
+    if unchanged < IDLE_SCANS_BEFORE_BACKOFF {
+        return base;
+    }
+    (current * 3 / 2).min(base.max(MAX_IDLE_INTERVAL))
+}
+
+/// Refresh triggers that didn't get a scan of their own
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerCounts {
+    /// Arrived while a triggered scan was already queued, and share it
+    pub coalesced: u64,
+}
+
+/// Where the poll thread is, as far as a new trigger cares
+const IDLE: u8 = 0;
+const QUEUED: u8 = 1;
+const SCANNING: u8 = 2;
+
+#[derive(Default)]
+struct TriggerState {
+    phase: AtomicU8,
+    coalesced: AtomicU64,
+}
+
+/// Enumeration on a background thread, every `interval` or on demand. The
+/// interval stretches while the device set stays the same and snaps back on
+/// the first scan that differs. The thread is stopped and joined on
+/// [`Poller::stop`] or drop, so no scan (or `lsusb` child) outlives its owner.
+pub struct Poller {
+    trigger: Sender<PollMessage>,
+    state: Arc<TriggerState>,
+    current_interval_us: Arc<AtomicU64>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Poller {
+    pub fn spawn(
+        backend: Backend,
+        ids: UsbIds,
+        rules: DfuRules,
+        lsusb: LsusbOptions,
+        interval: Duration,
+    ) -> (Self, Receiver<PollResult>) {
+        let (result_tx, result_rx) = mpsc::channel();
+        let (trigger, trigger_rx) = mpsc::channel();
+        let state = Arc::new(TriggerState::default());
+        let thread_state = Arc::clone(&state);
+        let current_interval_us = Arc::new(AtomicU64::new(interval.as_micros() as u64));
+        let thread_interval = Arc::clone(&current_interval_us);
+        let handle = thread::spawn(move || {
+            let state = thread_state;
+            let mut last_scan: Option<Instant> = None;
+            let mut current = interval;
+            let mut last_keys: Option<Vec<(String, Option<u64>)>> = None;
+            let mut unchanged = 0;
+            // Scan on a trigger or once the interval elapses; Stop or a dropped
+            // Poller ends the loop
+            loop {
+                let requested = match trigger_rx.recv_timeout(current) {
+                    Ok(PollMessage::Refresh(at)) => {
+                        // Too soon after the last scan: wait out the gap, still
+                        // listening for Stop
+                        let wait = last_scan
+                            .map(|at| MIN_SCAN_GAP.saturating_sub(at.elapsed()))
+                            .unwrap_or_default();
+                        if !wait.is_zero() {
+                            match trigger_rx.recv_timeout(wait) {
+                                Ok(PollMessage::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                                    break
+                                }
+                                _ => {}
+                            }
+                        }
+                        Some(at)
+                    }
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Ok(PollMessage::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                };
+                // This scan answers every trigger sent so far
+                if trigger_rx
+                    .try_iter()
+                    .any(|message| matches!(message, PollMessage::Stop))
+                {
+                    break;
+                }
+                state.phase.store(SCANNING, Ordering::SeqCst);
+                let start = Instant::now();
+                let poll = get_usb_devices(backend, &ids, &rules, &lsusb);
+                last_scan = Some(Instant::now());
+                // A trigger mid-scan has already queued the follow-up; keep it
+                let _ = state.phase.compare_exchange(
+                    SCANNING,
+                    IDLE,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+
+                // A rising URB count is a change too, so a busy device keeps the
+                // poll fast enough for its activity spinner
+                let keys: Vec<(String, Option<u64>)> = poll
+                    .devices
+                    .iter()
+                    .map(|d| (d.key(), d.urb_count))
+                    .collect();
+                if last_keys.as_ref() == Some(&keys) {
+                    unchanged += 1;
+                } else {
+                    unchanged = 0;
+                }
+                last_keys = Some(keys);
+                current = backed_off(current, interval, unchanged);
+                thread_interval.store(current.as_micros() as u64, Ordering::Relaxed);
+                let scan_time = start.elapsed();
+                if result_tx
+                    .send((poll, scan_time, requested.unwrap_or(start)))
+                    .is_err()
+                {
+                    break; // Receiver dropped
+                }
+            }
+        });
+        let poller = Self {
+            trigger,
+            state,
+            current_interval_us,
+            handle: Some(handle),
+        };
+        (poller, result_rx)
+    }
+
+    /// Scan now instead of waiting out the interval. A trigger while one is
+    /// already queued is folded into that scan; one while a scan runs queues
+    /// a single follow-up, since the scan in flight may have missed whatever
+    /// prompted it.
+    pub fn refresh(&self) {
+        let phase = &self.state.phase;
+        let mut current = phase.load(Ordering::SeqCst);
+        loop {
+            if current == QUEUED {
+                self.state.coalesced.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            // IDLE or SCANNING
+            match phase.compare_exchange(current, QUEUED, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => {
+                    let _ = self.trigger.send(PollMessage::Refresh(Instant::now()));
+                    return;
+                }
+                Err(now) => current = now,
+            }
+        }
+    }
+
+    /// Triggers folded into other scans so far
+    pub fn trigger_counts(&self) -> TriggerCounts {
+        TriggerCounts {
+            coalesced: self.state.coalesced.load(Ordering::Relaxed),
+        }
+    }
+
+    /// How long the thread waits between scans right now, the configured
+    /// interval unless it has backed off
+    pub fn current_interval(&self) -> Duration {
+        Duration::from_micros(self.current_interval_us.load(Ordering::Relaxed))
+    }
+
+    /// Stop the thread and wait for it, finishing any scan in progress
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.trigger.send(PollMessage::Stop);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn enumerate_from_lsusb(
+    lsusb: &LsusbOptions,
+    tty_map: &TtyMap,
+    rules: &DfuRules,
+    warnings: &mut Vec<String>,
+) -> (Vec<UsbDevice>, usize) {
+    match lsusb.run(&[]) {
+        Ok(stdout) => parse_lsusb_output(&stdout, tty_map, rules),
+        Err(err) => {
+            warnings.push(err);
+            (vec![], 0)
+        }
+    }
+}
+
+/// Parse every line of `lsusb` output, counting the non-blank lines that aren't devices
+pub fn parse_lsusb_output(
+    output: &str,
+    tty_map: &TtyMap,
+    rules: &DfuRules,
+) -> (Vec<UsbDevice>, usize) {
+    let mut devices = vec![];
+    let mut failures = 0;
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_lsusb_line(line, tty_map, rules) {
+            Some(device) => devices.push(device),
+            None => failures += 1,
+        }
+    }
+    (devices, failures)
+}
+
+/// Walk /sys/bus/usb/devices and build the device list without spawning anything
+fn enumerate_from_sysfs(
+    tty_map: &TtyMap,
+    rules: &DfuRules,
+    warnings: &mut Vec<String>,
+) -> Vec<UsbDevice> {
+    let entries = match fs::read_dir(SYSFS_USB_DEVICES) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warnings.push(format!("couldn't read {}: {}", SYSFS_USB_DEVICES, err));
+            return vec![];
+        }
+    };
+
+    let mut devices: Vec<UsbDevice> = entries
+        .flatten()
+        // Interface entries look like 1-3:1.0, we only want devices
+        .filter(|entry| !entry.file_name().to_string_lossy().contains(':'))
+        .filter_map(|entry| {
+            let device = parse_sysfs_device(&entry.path(), tty_map, rules);
+            if device.is_none() {
+                warnings.push(format!(
+                    "skipped {}: missing IDs or bus/device number",
+                    entry.file_name().to_string_lossy()
+                ));
+            }
+            device
+        })
+        .collect();
+
+    // read_dir order is arbitrary, keep the list stable between polls
+    devices.sort_by_key(|d| {
+        (
+            d.bus.parse::<u32>().unwrap_or(0),
+            d.device.parse::<u32>().unwrap_or(0),
+        )
+    });
+    devices
+}
+
+fn parse_sysfs_device(dir: &Path, tty_map: &TtyMap, rules: &DfuRules) -> Option<UsbDevice> {
+    let vendor_id = read_sysfs_attr(dir, "idVendor")?;
+    let product_id = read_sysfs_attr(dir, "idProduct")?;
+    let bus_num: u32 = read_sysfs_attr(dir, "busnum")?.parse().ok()?;
+    let dev_num: u32 = read_sysfs_attr(dir, "devnum")?.parse().ok()?;
+
+    // Optional attributes: absent is normal, present but unreadable is worth a note
+    let mut diagnostics = vec![];
+    let mut attr = |name: &str| read_sysfs_attr_noting(dir, name, &mut diagnostics);
+    let manufacturer = attr("manufacturer");
+    let serial = attr("serial");
+
+    // Match lsusb's "Vendor Product" naming as closely as the strings allow
+    let name = [manufacturer.clone(), attr("product")]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let name = if name.is_empty() {
+        "Unknown".to_string()
+    } else {
+        name
+    };
+
+    // Zero-pad like lsusb so keys and paths are identical across backends
+    let bus = format!("{:03}", bus_num);
+    let device = format!("{:03}", dev_num);
+    // The interface descriptor is authoritative, the name is only a fallback
+    let is_dfu = has_dfu_interface(dir).unwrap_or_else(|| rules.matches_name(&name));
+    let speed = attr("speed").and_then(|s| UsbSpeed::from_sysfs(&s));
+    let (class_code, subclass_code) = read_device_class(dir);
+    let drivers = read_interface_drivers(dir);
+    let interface_classes: Vec<u8> = interface_dirs(dir)
+        .iter()
+        .filter_map(|interface| read_sysfs_hex(interface, "bInterfaceClass"))
+        .collect();
+    let max_power_ma = read_max_power(dir);
+    let fw_version = attr("bcdDevice").and_then(|bcd| format_bcd(&bcd));
+    // Already "3.20", with a leading space that read_sysfs_attr trims
+    let usb_version = attr("version");
+    let max_packet_size0 = attr("bMaxPacketSize0").and_then(|size| size.parse().ok());
+    let endpoint_kinds: Vec<String> = interface_dirs(dir)
+        .iter()
+        .flat_map(|interface| interface_endpoints(interface))
+        .map(|endpoint| endpoint_kind(&endpoint))
+        .collect();
+    let endpoint_count = endpoint_kinds.len();
+    let urb_count = attr("urbnum").and_then(|n| n.parse().ok());
+    let authorized = attr("authorized").map(|value| value != "0");
+    let runtime_status = attr("power/runtime_status");
+    let port_path = dir
+        .file_name()
+        .map(|name| parse_port_path(&name.to_string_lossy()))
+        .unwrap_or_default();
+    let dev_path = format!("/dev/bus/usb/{}/{}", bus, device);
+    let tty_paths = tty_map
+        .get(&(bus_num, dev_num))
+        .cloned()
+        .unwrap_or_default();
+
+    Some(UsbDevice {
+        bus,
+        device,
+        vendor_id,
+        product_id,
+        name,
+        vendor_name: None,
+        product_name: None,
+        manufacturer,
+        serial,
+        fw_version,
+        usb_version,
+        max_packet_size0,
+        is_dfu,
+        speed,
+        drivers,
+        class_code,
+        subclass_code,
+        interface_classes,
+        max_power_ma,
+        port_path,
+        endpoint_count,
+        endpoint_kinds,
+        urb_count,
+        authorized,
+        runtime_status,
+        diagnostics,
+        dev_path,
+        tty_paths,
+        sysfs_dir: Some(dir.to_path_buf()),
+    })
+}
+
+/// Manufacturer, product, serial and the diagnostics from reading them
+#[cfg(feature = "rusb")]
+type RusbStrings = (Option<String>, Option<String>, Option<String>, Vec<String>);
+
+/// Bus, address, vendor and product ID
+#[cfg(feature = "rusb")]
+type RusbKey = (u8, u8, u16, u16);
+
+/// String descriptors from earlier scans by bus, address and VID:PID, so each
+/// device is opened once rather than on every poll. A replug gets a new
+/// address, and with it a fresh read.
+#[cfg(feature = "rusb")]
+static RUSB_STRINGS: LazyLock<std::sync::Mutex<HashMap<RusbKey, RusbStrings>>> =
+    LazyLock::new(Default::default);
+
+/// List devices through libusb. Enumeration only needs descriptors the OS
+/// caches; the strings mean opening each device, which can be refused without
+/// costing anything else.
+#[cfg(feature = "rusb")]
+fn enumerate_from_rusb(
+    tty_map: &TtyMap,
+    rules: &DfuRules,
+    warnings: &mut Vec<String>,
+) -> Vec<UsbDevice> {
+    use rusb::UsbContext;
+
+    // Not rusb::devices(): the global context panics when libusb can't start
+    let list = match rusb::Context::new().and_then(|context| context.devices()) {
+        Ok(list) => list,
+        Err(err) => {
+            warnings.push(format!("couldn't list devices through libusb: {}", err));
+            return vec![];
+        }
+    };
+
+    let mut strings = RUSB_STRINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut present = vec![];
+    let mut devices: Vec<UsbDevice> = list
+        .iter()
+        .filter_map(|device| {
+            let parsed = parse_rusb_device(&device, tty_map, rules, &mut strings, &mut present);
+            if parsed.is_none() {
+                warnings.push(format!(
+                    "skipped bus {} device {}: no device descriptor",
+                    device.bus_number(),
+                    device.address()
+                ));
+            }
+            parsed
+        })
+        .collect();
+    strings.retain(|key, _| present.contains(key));
+
+    devices.sort_by_key(|d| {
+        (
+            d.bus.parse::<u32>().unwrap_or(0),
+            d.device.parse::<u32>().unwrap_or(0),
+        )
+    });
+    devices
+}
+
+/// `strings` caches descriptor strings across scans; `present` collects the
+/// keys seen in this one so departed devices can be dropped from it.
+#[cfg(feature = "rusb")]
+fn parse_rusb_device<T: rusb::UsbContext>(
+    device: &rusb::Device<T>,
+    tty_map: &TtyMap,
+    rules: &DfuRules,
+    strings: &mut HashMap<RusbKey, RusbStrings>,
+    present: &mut Vec<RusbKey>,
+) -> Option<UsbDevice> {
+    let descriptor = device.device_descriptor().ok()?;
+    let bus_num = u32::from(device.bus_number());
+    let dev_num = u32::from(device.address());
+
+    let key = (
+        device.bus_number(),
+        device.address(),
+        descriptor.vendor_id(),
+        descriptor.product_id(),
+    );
+    present.push(key);
+    let (manufacturer, product, serial, diagnostics) = strings
+        .entry(key)
+        .or_insert_with(|| {
+            let mut diagnostics = vec![];
+            let (manufacturer, product, serial) =
+                read_rusb_strings(device, &descriptor, &mut diagnostics);
+            (manufacturer, product, serial, diagnostics)
+        })
+        .clone();
+    let name = [manufacturer.clone(), product]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let name = if name.is_empty() {
+        "Unknown".to_string()
+    } else {
+        name
+    };
+
+    // Alternate setting 0 of each interface, which is what sysfs shows too
+    let config = device.active_config_descriptor().ok();
+    let interfaces: Vec<rusb::InterfaceDescriptor> = config
+        .iter()
+        .flat_map(|config| config.interfaces())
+        .filter_map(|interface| interface.descriptors().next())
+        .collect();
+    let interface_classes: Vec<u8> = interfaces.iter().map(|i| i.class_code()).collect();
+    let is_dfu = if interfaces.is_empty() {
+        rules.matches_name(&name)
+    } else {
+        interfaces
+            .iter()
+            .any(|i| i.class_code() == 0xfe && i.sub_class_code() == 0x01)
+    };
+    let (class_code, subclass_code) = match (descriptor.class_code(), interfaces.first()) {
+        (0x00, Some(interface)) => (
+            Some(interface.class_code()),
+            Some(interface.sub_class_code()),
+        ),
+        (class, _) => (Some(class), Some(descriptor.sub_class_code())),
+    };
+    let endpoint_kinds: Vec<String> = interfaces
+        .iter()
+        .flat_map(|interface| interface.endpoint_descriptors())
+        .map(|endpoint| {
+            let direction = match endpoint.direction() {
+                rusb::Direction::In => "IN",
+                rusb::Direction::Out => "OUT",
+            };
+            let kind = match endpoint.transfer_type() {
+                rusb::TransferType::Control => "control",
+                rusb::TransferType::Isochronous => "isoc",
+                rusb::TransferType::Bulk => "bulk",
+                rusb::TransferType::Interrupt => "interrupt",
+            };
+            format!("{} {}", direction, kind)
+        })
+        .collect();
+    let speed = match device.speed() {
+        rusb::Speed::Low => Some(UsbSpeed::Low),
+        rusb::Speed::Full => Some(UsbSpeed::Full),
+        rusb::Speed::High => Some(UsbSpeed::High),
+        rusb::Speed::Super => Some(UsbSpeed::Super),
+        rusb::Speed::SuperPlus => Some(UsbSpeed::SuperPlus),
+        _ => None,
+    };
+    let bcd = |version: rusb::Version| {
+        format!(
+            "{}.{}{}",
+            version.major(),
+            version.minor(),
+            version.sub_minor()
+        )
+    };
+
+    let bus = format!("{:03}", bus_num);
+    let dev = format!("{:03}", dev_num);
+    let dev_path = format!("/dev/bus/usb/{}/{}", bus, dev);
+    let tty_paths = tty_map
+        .get(&(bus_num, dev_num))
+        .cloned()
+        .unwrap_or_default();
+
+    Some(UsbDevice {
+        bus,
+        device: dev,
+        vendor_id: format!("{:04x}", descriptor.vendor_id()),
+        product_id: format!("{:04x}", descriptor.product_id()),
+        name,
+        vendor_name: None,
+        product_name: None,
+        manufacturer,
+        serial,
+        fw_version: Some(bcd(descriptor.device_version())),
+        usb_version: Some(bcd(descriptor.usb_version())),
+        max_packet_size0: Some(descriptor.max_packet_size()),
+        is_dfu,
+        speed,
+        // libusb can say a kernel driver is attached, but not which one
+        drivers: vec![],
+        class_code,
+        subclass_code,
+        interface_classes,
+        max_power_ma: config.as_ref().map(|config| {
+            // rusb assumes 2 mA units, but SuperSpeed counts bMaxPower in 8 mA
+            if matches!(
+                speed,
+                Some(UsbSpeed::Super | UsbSpeed::SuperPlus | UsbSpeed::SuperPlus2)
+            ) {
+                config.max_power() * 4
+            } else {
+                config.max_power()
+            }
+        }),
+        port_path: device.port_numbers().unwrap_or_default(),
+        endpoint_count: endpoint_kinds.len(),
+        endpoint_kinds,
+        urb_count: None,
+        authorized: None,
+        runtime_status: None,
+        diagnostics,
+        dev_path,
+        tty_paths,
+        sysfs_dir: None,
+    })
+}
+
+/// Manufacturer, product and serial string descriptors. A device we may not
+/// open (no udev rule, or the OS holds it) leaves a diagnostic instead.
+#[cfg(feature = "rusb")]
+fn read_rusb_strings<T: rusb::UsbContext>(
+    device: &rusb::Device<T>,
+    descriptor: &rusb::DeviceDescriptor,
+    diagnostics: &mut Vec<String>,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let has_strings = descriptor.manufacturer_string_index().is_some()
+        || descriptor.product_string_index().is_some()
+        || descriptor.serial_number_string_index().is_some();
+    if !has_strings {
+        return (None, None, None);
+    }
+    let handle = match device.open() {
+        Ok(handle) => handle,
+        Err(err) => {
+            diagnostics.push(format!("strings: {}", err));
+            return (None, None, None);
+        }
+    };
+    let mut note = |field: &str, result: rusb::Result<String>| match result {
+        Ok(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+        Err(err) => {
+            diagnostics.push(format!("{}: {}", field, err));
+            None
+        }
+    };
+    let manufacturer = descriptor.manufacturer_string_index().and_then(|_| {
+        note(
+            "manufacturer",
+            handle.read_manufacturer_string_ascii(descriptor),
+        )
+    });
+    let product = descriptor
+        .product_string_index()
+        .and_then(|_| note("product", handle.read_product_string_ascii(descriptor)));
+    let serial = descriptor
+        .serial_number_string_index()
+        .and_then(|_| note("serial", handle.read_serial_number_string_ascii(descriptor)));
+    (manufacturer, product, serial)
+}
+
+/// Ports from a sysfs device name: "1-3.2.1" is bus 1, port 3, then port 2
+/// of that hub, then port 1. Root hubs ("usb1") have no ports.
+fn parse_port_path(name: &str) -> Vec<u8> {
+    let Some((_bus, ports)) = name.split_once('-') else {
+        return vec![];
+    };
+    ports
+        .split('.')
+        .filter_map(|port| port.parse().ok())
+        .collect()
+}
+
+/// `read_sysfs_attr`, but a node that's there and failed to read is noted in
+/// `diagnostics` instead of passing for one that doesn't exist
+fn read_sysfs_attr_noting(dir: &Path, attr: &str, diagnostics: &mut Vec<String>) -> Option<String> {
+    match fs::read_to_string(dir.join(attr)) {
+        Ok(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => {
+            diagnostics.push(format!("{}: {}", attr, err));
+            None
+        }
+    }
+}
+
+/// Read a single sysfs attribute file, trimmed. Missing or empty files give None.
+fn read_sysfs_attr(dir: &Path, attr: &str) -> Option<String> {
+    let value = fs::read_to_string(dir.join(attr)).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Interface directories (e.g. 1-3:1.0) of the active configuration
+fn interface_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut dirs: Vec<_> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().contains(':'))
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Check interfaces for the DFU class (0xFE application-specific, subclass 0x01).
+/// None when the device exposes no readable interfaces to decide from.
+fn has_dfu_interface(dir: &Path) -> Option<bool> {
+    let mut saw_interface = false;
+    for interface in interface_dirs(dir) {
+        let Some(class) = read_sysfs_attr(&interface, "bInterfaceClass") else {
+            continue;
+        };
+        saw_interface = true;
+        let subclass = read_sysfs_attr(&interface, "bInterfaceSubClass");
+        if class.eq_ignore_ascii_case("fe") && subclass.as_deref() == Some("01") {
+            return Some(true);
+        }
+    }
+    saw_interface.then_some(false)
+}
+
+/// Find a device's sysfs directory by bus and device number
+pub fn find_sysfs_dir(bus: &str, device: &str) -> Option<PathBuf> {
+    let bus: u32 = bus.parse().ok()?;
+    let device: u32 = device.parse().ok()?;
+    fs::read_dir(SYSFS_USB_DEVICES)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|dir| {
+            read_sysfs_attr(dir, "busnum").and_then(|b| b.parse().ok()) == Some(bus)
+                && read_sysfs_attr(dir, "devnum").and_then(|d| d.parse().ok()) == Some(device)
+        })
+}
+
+/// A device's sysfs directory, unless it comes from a captured listing
+/// (`LsusbOptions::file`): those bus and device numbers belong to another
+/// machine, and here they'd name some unrelated device
+fn replayable_sysfs_dir(device: &UsbDevice, lsusb: &LsusbOptions) -> Option<PathBuf> {
+    if lsusb.file.is_some() {
+        return None;
+    }
+    device.locate_sysfs_dir()
+}
+
+/// Full descriptor dump for a device: `lsusb -v` when it works, otherwise
+/// a configuration/interface/endpoint tree built from sysfs
+pub fn descriptor_lines(device: &UsbDevice, lsusb: &LsusbOptions) -> Vec<String> {
+    let args = [
+        "-v".to_string(),
+        "-s".to_string(),
+        format!("{}:{}", device.bus, device.device),
+    ];
+    let failure = match lsusb.run(&args) {
+        Ok(stdout) if !stdout.trim().is_empty() => {
+            return stdout.lines().map(str::to_string).collect();
+        }
+        Ok(_) => None,
+        Err(err) => Some(err),
+    };
+
+    match (replayable_sysfs_dir(device, lsusb), failure) {
+        (Some(dir), _) => sysfs_descriptor_tree(&dir),
+        (None, Some(err)) => vec!["No descriptor information available".to_string(), err],
+        (None, None) => vec!["No descriptor information available".to_string()],
+    }
+}
+
+/// The DFU functional descriptor that follows a DFU interface descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfuDescriptor {
+    /// bmAttributes: bit 0 download, 1 upload, 2 manifestation tolerant, 3 will detach
+    pub attributes: u8,
+    /// wDetachTimeOut: how long the device waits for a reset after DFU_DETACH
+    pub detach_timeout_ms: u16,
+    /// wTransferSize: the most a single DFU_DNLOAD/DFU_UPLOAD request carries
+    pub transfer_size: u16,
+    /// bcdDFUVersion, missing from some DFU 1.0 devices' shorter descriptor
+    pub dfu_version: Option<u16>,
+}
+
+impl DfuDescriptor {
+    /// What the set bmAttributes bits allow, lowest bit first
+    pub fn capabilities(&self) -> Vec<&'static str> {
+        [
+            "download",
+            "upload",
+            "manifestation tolerant",
+            "will detach",
+        ]
+        .into_iter()
+        .enumerate()
+        .filter(|(bit, _)| self.attributes & (1 << bit) != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
+
+    /// bcdDFUVersion as lsusb prints it: "1.10", or "1.1a" for ST's DfuSe
+    pub fn version(&self) -> Option<String> {
+        self.dfu_version
+            .map(|bcd| format!("{:x}.{:02x}", bcd >> 8, bcd & 0xff))
+    }
+}
+
+/// Functional descriptor of a device's DFU interface, from the raw
+/// `descriptors` file in sysfs, else from `lsusb -v`
+pub fn dfu_descriptor(device: &UsbDevice, lsusb: &LsusbOptions) -> Option<DfuDescriptor> {
+    let raw =
+        replayable_sysfs_dir(device, lsusb).and_then(|dir| fs::read(dir.join("descriptors")).ok());
+    if let Some(descriptor) = raw.as_deref().and_then(parse_dfu_descriptors) {
+        return Some(descriptor);
+    }
+    let args = [
+        "-v".to_string(),
+        "-s".to_string(),
+        format!("{}:{}", device.bus, device.device),
+    ];
+    parse_dfu_lsusb(&lsusb.run(&args).ok()?)
+}
+
+/// Walk a raw descriptor blob (device descriptor, then each configuration's)
+/// for a functional descriptor (type 0x21) inside a DFU interface. HID uses
+/// 0x21 too, hence tracking which interface it belongs to.
+pub fn parse_dfu_descriptors(raw: &[u8]) -> Option<DfuDescriptor> {
+    let mut rest = raw;
+    let mut in_dfu_interface = false;
+    while let [length, kind, ..] = *rest {
+        let length = usize::from(length);
+        if length < 2 || length > rest.len() {
+            break;
+        }
+        let descriptor = &rest[..length];
+        match kind {
+            0x04 if length >= 7 => {
+                in_dfu_interface = descriptor[5] == 0xfe && descriptor[6] == 0x01
+            }
+            0x21 if in_dfu_interface && length >= 7 => {
+                let word = |at: usize| u16::from_le_bytes([descriptor[at], descriptor[at + 1]]);
+                return Some(DfuDescriptor {
+                    attributes: descriptor[2],
+                    detach_timeout_ms: word(3),
+                    transfer_size: word(5),
+                    dfu_version: (length >= 9).then(|| word(7)),
+                });
+            }
+            _ => {}
+        }
+        rest = &rest[length..];
+    }
+    None
+}
+
+/// The "Device Firmware Upgrade Interface Descriptor" block of `lsusb -v`
+pub fn parse_dfu_lsusb(verbose: &str) -> Option<DfuDescriptor> {
+    let mut lines = verbose
+        .lines()
+        .skip_while(|line| !line.contains("Device Firmware Upgrade Interface Descriptor"))
+        .skip(1)
+        .take_while(|line| !line.trim_end().ends_with("Descriptor:"));
+    // Sequential, in the order lsusb prints them
+    let mut field = |name: &str| -> Option<String> {
+        let line = lines.find(|line| line.split_whitespace().next() == Some(name))?;
+        line.split_whitespace().nth(1).map(str::to_string)
+    };
+    let attributes = field("bmAttributes")?.parse().ok()?;
+    let detach_timeout_ms = field("wDetachTimeout")?.parse().ok()?;
+    let transfer_size = field("wTransferSize")?.parse().ok()?;
+    let dfu_version = field("bcdDFUVersion").and_then(|version| {
+        let (major, minor) = version.split_once('.')?;
+        Some(u16::from_str_radix(major, 16).ok()? << 8 | u16::from_str_radix(minor, 16).ok()?)
+    });
+    Some(DfuDescriptor {
+        attributes,
+        detach_timeout_ms,
+        transfer_size,
+        dfu_version,
+    })
+}
+
+fn sysfs_descriptor_tree(dir: &Path) -> Vec<String> {
+    let attr = |dir: &Path, name: &str| read_sysfs_attr(dir, name).unwrap_or_else(|| "?".into());
+    let mut lines = vec![
+        format!(
+            "Device {}:{}  {}",
+            attr(dir, "idVendor"),
+            attr(dir, "idProduct"),
+            attr(dir, "product")
+        ),
+        format!(
+            "  bcdUSB {}  bcdDevice {}  class {}:{}:{}  bMaxPacketSize0 {}",
+            attr(dir, "version"),
+            attr(dir, "bcdDevice"),
+            attr(dir, "bDeviceClass"),
+            attr(dir, "bDeviceSubClass"),
+            attr(dir, "bDeviceProtocol"),
+            attr(dir, "bMaxPacketSize0")
+        ),
+        format!(
+            "  Configuration {}  bNumInterfaces {}  MaxPower {}",
+            attr(dir, "bConfigurationValue"),
+            attr(dir, "bNumInterfaces"),
+            attr(dir, "bMaxPower")
+        ),
+    ];
+
+    for interface in interface_dirs(dir) {
+        let name = interface
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let driver = read_driver(&interface).unwrap_or_else(|| "(none)".into());
+        lines.push(format!(
+            "    Interface {}  alt {}  class {}:{}:{}  driver {}",
+            name,
+            attr(&interface, "bAlternateSetting"),
+            attr(&interface, "bInterfaceClass"),
+            attr(&interface, "bInterfaceSubClass"),
+            attr(&interface, "bInterfaceProtocol"),
+            driver
+        ));
+        if let Some(label) = read_sysfs_attr(&interface, "interface") {
+            lines.push(format!("      \"{}\"", label));
+        }
+
+        for endpoint in interface_endpoints(&interface) {
+            lines.push(format!(
+                "      Endpoint {}  {} {}  wMaxPacketSize {}  bInterval {}",
+                attr(&endpoint, "bEndpointAddress"),
+                attr(&endpoint, "direction"),
+                attr(&endpoint, "type"),
+                attr(&endpoint, "wMaxPacketSize"),
+                attr(&endpoint, "bInterval")
+            ));
+        }
+    }
+
+    lines
+}
+
+/// Endpoint directories (ep_81, ep_02, ...) of an interface, in address order
+fn interface_endpoints(interface: &Path) -> Vec<PathBuf> {
+    let mut endpoints: Vec<PathBuf> = fs::read_dir(interface)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("ep_"))
+        .map(|entry| entry.path())
+        .collect();
+    endpoints.sort();
+    endpoints
+}
+
+/// "IN bulk" from an endpoint's `direction` and `type` attributes
+fn endpoint_kind(endpoint: &Path) -> String {
+    let direction = read_sysfs_attr(endpoint, "direction").unwrap_or_else(|| "?".into());
+    let kind = read_sysfs_attr(endpoint, "type").unwrap_or_else(|| "?".into());
+    format!("{} {}", direction.to_uppercase(), kind.to_lowercase())
+}
+
+/// Name of the driver bound to a sysfs device/interface, from its `driver` symlink
+fn read_driver(dir: &Path) -> Option<String> {
+    let target = fs::read_link(dir.join("driver")).ok()?;
+    Some(target.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Distinct drivers bound across all interfaces, in interface order
+fn read_interface_drivers(dir: &Path) -> Vec<String> {
+    let mut drivers: Vec<String> = vec![];
+    for driver in interface_dirs(dir).iter().filter_map(|i| read_driver(i)) {
+        if !drivers.contains(&driver) {
+            drivers.push(driver);
+        }
+    }
+    drivers
+}
+
+/// Parse a two-digit hex sysfs attribute like bDeviceClass
+fn read_sysfs_hex(dir: &Path, attr: &str) -> Option<u8> {
+    u8::from_str_radix(&read_sysfs_attr(dir, attr)?, 16).ok()
+}
+
+/// Device class and subclass. Class 0x00 means "defined per interface", so
+/// fall back to the primary interface in that case.
+fn read_device_class(dir: &Path) -> (Option<u8>, Option<u8>) {
+    let class = read_sysfs_hex(dir, "bDeviceClass");
+    if class != Some(0x00) {
+        return (class, read_sysfs_hex(dir, "bDeviceSubClass"));
+    }
+
+    match interface_dirs(dir).first() {
+        Some(interface) => (
+            read_sysfs_hex(interface, "bInterfaceClass").or(class),
+            read_sysfs_hex(interface, "bInterfaceSubClass"),
+        ),
+        None => (class, read_sysfs_hex(dir, "bDeviceSubClass")),
+    }
+}
+
+/// "0102" -> "1.02": two BCD digits of major, two of minor. The major
+/// part loses its leading zero, the minor part keeps both digits.
+pub fn format_bcd(bcd: &str) -> Option<String> {
+    if bcd.len() != 4 || !bcd.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let (major, minor) = bcd.split_at(2);
+    let major = major.strip_prefix('0').unwrap_or(major);
+    Some(format!("{}.{}", major, minor.to_lowercase()))
+}
+
+/// Current draw in mA. The kernel prints bMaxPower already scaled (e.g.
+/// "500mA"); a bare number is the raw one-byte descriptor value in 2 mA
+/// units, so anything above 255 is garbage.
+fn read_max_power(dir: &Path) -> Option<u16> {
+    let raw = read_sysfs_attr(dir, "bMaxPower")?;
+    match raw.strip_suffix("mA") {
+        Some(ma) => ma.trim().parse().ok(),
+        None => raw.parse::<u8>().ok().map(|units| u16::from(units) * 2),
+    }
+}
+
+/// What a hub's downstream devices draw against what it can supply
+pub struct HubPower {
+    pub draw_ma: u32,
+    pub budget_ma: u32,
+}
+
+/// Sum bMaxPower over the devices plugged directly into a hub. A
+/// self-powered hub can give every port the full per-port current; a
+/// bus-powered one has to share what its own upstream port provides.
+pub fn hub_power(dir: &Path, speed: Option<UsbSpeed>) -> Option<HubPower> {
+    let draw_ma = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().contains(':'))
+        .filter_map(|entry| read_max_power(&entry.path()))
+        .map(u32::from)
+        .sum();
+
+    let port_ma = match speed {
+        Some(UsbSpeed::Super | UsbSpeed::SuperPlus | UsbSpeed::SuperPlus2) => 900,
+        _ => 500,
+    };
+    let self_powered = read_sysfs_hex(dir, "bmAttributes").is_some_and(|attrs| attrs & 0x40 != 0);
+    let budget_ma = if self_powered {
+        let ports: u32 = read_sysfs_attr(dir, "maxchild")?.parse().ok()?;
+        ports * port_ma
+    } else {
+        port_ma
+    };
+    Some(HubPower { draw_ma, budget_ma })
+}
+
+/// One downstream port of a hub, from its `<hub>-portN` sysfs directory
+pub struct HubPort {
+    pub number: u8,
+    /// Port path of whatever is plugged in ("1-3.2"), None when empty
+    pub device: Option<String>,
+    /// `connect_type`: "hotplug", "hardwired", "not used" or "unknown"
+    pub connect_type: Option<String>,
+    /// `disable`: true while the kernel has the port switched off. Whether
+    /// that really cuts VBUS depends on the hub; see [`hub_power_switching`].
+    pub disabled: Option<bool>,
+    /// Whether the port has power: false when disabled or runtime-suspended,
+    /// None when sysfs doesn't say
+    pub powered: Option<bool>,
+    pub over_current_count: Option<u32>,
+}
+
+/// Downstream ports of a hub, in port order. They hang off the hub's
+/// interface, e.g. /sys/bus/usb/devices/1-3:1.0/1-3-port2.
+pub fn hub_ports(dir: &Path) -> Vec<HubPort> {
+    let mut ports: Vec<HubPort> = interface_dirs(dir)
+        .iter()
+        .filter_map(|interface| fs::read_dir(interface).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let number = name.rsplit_once("-port")?.1.parse().ok()?;
+            let port = entry.path();
+            Some(HubPort {
+                number,
+                device: fs::read_link(port.join("device"))
+                    .ok()
+                    .and_then(|target| Some(target.file_name()?.to_string_lossy().into_owned())),
+                connect_type: read_sysfs_attr(&port, "connect_type"),
+                disabled: read_sysfs_attr(&port, "disable").map(|value| value == "1"),
+                powered: match read_sysfs_attr(&port, "disable").as_deref() {
+                    Some("1") => Some(false),
+                    _ => match read_sysfs_attr(&port, "power/runtime_status").as_deref() {
+                        Some("active") => Some(true),
+                        Some("suspended") => Some(false),
+                        _ => None,
+                    },
+                },
+                over_current_count: read_sysfs_attr(&port, "over_current_count")
+                    .and_then(|count| count.parse().ok()),
+            })
+        })
+        .collect();
+    ports.sort_by_key(|port| port.number);
+    ports
+}
+
+/// Human-readable name for a USB-IF base class code
+pub fn class_label(code: u8) -> &'static str {
+    match code {
+        0x00 => "Per-interface",
+        0x01 => "Audio",
+        0x02 => "Communications (CDC)",
+        0x03 => "HID",
+        0x05 => "Physical",
+        0x06 => "Image",
+        0x07 => "Printer",
+        0x08 => "Mass Storage",
+        0x09 => "Hub",
+        0x0a => "CDC Data",
+        0x0b => "Smart Card",
+        0x0d => "Content Security",
+        0x0e => "Video",
+        0x0f => "Personal Healthcare",
+        0x10 => "Audio/Video",
+        0x11 => "Billboard",
+        0x12 => "USB-C Bridge",
+        0xdc => "Diagnostic",
+        0xe0 => "Wireless Controller",
+        0xef => "Miscellaneous",
+        0xfe => "Application Specific",
+        0xff => "Vendor Specific",
+        _ => "Unknown",
+    }
+}
+
+/// "Bus 001 Device 002: ID 1234:5678 Name". Anchored at the start and
+/// requiring a hex VID:PID, so an "ID" inside the name can't be mistaken for it
+static LSUSB_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^\s*bus\s+(\d+)\s+device\s+(\d+)(?:\s*:\s*|\s+)id\s+([0-9a-f]{4}):([0-9a-f]{4})(?:\s+(.*))?$",
+    )
+    .expect("valid lsusb line pattern")
+});
+
+/// Parse one line of `lsusb` output: "Bus 001 Device 002: ID 1234:5678 Name".
+/// Busybox and some locales differ in the details, so unpadded numbers,
+/// extra spaces, a detached colon and a missing name are all accepted.
+pub fn parse_lsusb_line(line: &str, tty_map: &TtyMap, rules: &DfuRules) -> Option<UsbDevice> {
+    let captures = LSUSB_LINE.captures(line.trim_end())?;
+    let bus_num: u32 = captures[1].parse().ok()?;
+    let dev_num: u32 = captures[2].parse().ok()?;
+    let vendor_id = captures[3].to_lowercase();
+    let product_id = captures[4].to_lowercase();
+
+    // Whatever follows the ID is the name, internal spacing and all
+    let name = captures.get(5).map_or("", |name| name.as_str().trim());
+    let name = if name.is_empty() {
+        "Unknown".to_string()
+    } else {
+        name.to_string()
+    };
+
+    // Zero-pad like sysfs does so keys match whichever lsusb printed them
+    let bus = format!("{:03}", bus_num);
+    let device = format!("{:03}", dev_num);
+
+    let is_dfu = rules.matches_name(&name);
+    // lsusb doesn't report negotiated speed or descriptor strings
+    let speed = None;
+    let manufacturer = None;
+    let serial = None;
+    let (class_code, subclass_code) = (None, None);
+    let interface_classes = vec![];
+    let max_power_ma = None;
+    let fw_version = None;
+    let usb_version = None;
+    let max_packet_size0 = None;
+    // lsusb's flat listing doesn't say which port a device hangs off
+    let port_path = vec![];
+    let (endpoint_count, endpoint_kinds) = (0, vec![]);
+    let urb_count = None;
+    let (authorized, runtime_status) = (None, None);
+    let diagnostics = vec![];
+    let drivers = vec![];
+
+    // Build /dev/bus/usb path
+    let dev_path = format!("/dev/bus/usb/{}/{}", bus, device);
+
+    // Look up tty path
+    let tty_paths = tty_map
+        .get(&(bus_num, dev_num))
+        .cloned()
+        .unwrap_or_default();
+
+    Some(UsbDevice {
+        bus,
+        device,
+        vendor_id,
+        product_id,
+        name,
+        vendor_name: None,
+        product_name: None,
+        manufacturer,
+        serial,
+        fw_version,
+        usb_version,
+        max_packet_size0,
+        is_dfu,
+        speed,
+        drivers,
+        class_code,
+        subclass_code,
+        interface_classes,
+        max_power_ma,
+        port_path,
+        endpoint_count,
+        endpoint_kinds,
+        urb_count,
+        authorized,
+        runtime_status,
+        diagnostics,
+        dev_path,
+        tty_paths,
+        sysfs_dir: None,
+    })
+}
+
+/// `_IO('U', 20)` from linux/usbdevice_fs.h
+const USBDEVFS_RESET: u64 = 0x5514;
+
+/// `struct usbdevfs_ctrltransfer` from linux/usbdevice_fs.h
+#[repr(C)]
+struct CtrlTransfer {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+    timeout_ms: u32,
+    data: *mut libc::c_void,
+}
+
+/// `_IOWR('U', 0, struct usbdevfs_ctrltransfer)`
+const USBDEVFS_CONTROL: u64 =
+    (3 << 30) | ((std::mem::size_of::<CtrlTransfer>() as u64) << 16) | ((b'U' as u64) << 8);
+
+/// How a hub switches power to its downstream ports: the logical power
+/// switching mode, bits 1:0 of wHubCharacteristics in its hub descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSwitching {
+    /// All ports at once
+    Ganged,
+    /// Each port on its own, what uhubctl-style power cycling needs
+    PerPort,
+    /// Ports are always powered
+    None,
+}
+
+/// Read a hub's power switching mode from its hub descriptor. None when the
+/// descriptor can't be fetched, usually for lack of write access to the node.
+pub fn hub_power_switching(device: &UsbDevice) -> Option<PowerSwitching> {
+    use std::os::fd::AsRawFd;
+
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&device.dev_path)
+        .ok()?;
+    // SuperSpeed hubs have their own descriptor type; the field sits at the
+    // same offset in both
+    let descriptor_type: u16 = match device.speed {
+        Some(UsbSpeed::Super | UsbSpeed::SuperPlus | UsbSpeed::SuperPlus2) => 0x2a,
+        _ => 0x29,
+    };
+    let mut buf = [0u8; 16];
+    let mut transfer = CtrlTransfer {
+        request_type: 0xa0, // Device-to-host, class, device
+        request: 0x06,      // GET_DESCRIPTOR
+        value: descriptor_type << 8,
+        index: 0,
+        length: buf.len() as u16,
+        timeout_ms: 500,
+        data: buf.as_mut_ptr().cast(),
+    };
+    // SAFETY: `transfer` points at `buf`, which outlives the call and holds `length` bytes
+    let received = unsafe { libc::ioctl(file.as_raw_fd(), USBDEVFS_CONTROL as _, &mut transfer) };
+    if received < 5 {
+        return None;
+    }
+    Some(match buf[3] & 0b11 {
+        0b00 => PowerSwitching::Ganged,
+        0b01 => PowerSwitching::PerPort,
+        _ => PowerSwitching::None,
+    })
+}
+
+/// Issue a USB port reset on a /dev/bus/usb node, as if the device was replugged
+pub fn reset_device(dev_path: &str) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let file = fs::OpenOptions::new().write(true).open(dev_path)?;
+    // SAFETY: USBDEVFS_RESET takes no argument and the fd is valid for the call
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), USBDEVFS_RESET as _, 0) };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Authorize or deauthorize a device by writing its sysfs `authorized` node.
+/// Deauthorizing unbinds every driver until it's authorized again.
+pub fn set_authorized(device: &UsbDevice, authorized: bool) -> std::io::Result<()> {
+    let dir = device.locate_sysfs_dir().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "no sysfs entry for device")
+    })?;
+    fs::write(dir.join("authorized"), if authorized { "1" } else { "0" })
+}
+
+/// An interface with a kernel driver bound to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundInterface {
+    /// sysfs interface name such as "1-3:1.0", which is what `unbind` takes
+    pub interface: String,
+    pub driver: String,
+}
+
+/// A device's interfaces that currently have a driver, in interface order
+pub fn bound_interfaces(device: &UsbDevice) -> std::io::Result<Vec<BoundInterface>> {
+    let dir = device.locate_sysfs_dir().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "no sysfs entry for device")
+    })?;
+    Ok(interface_dirs(&dir)
+        .iter()
+        .filter_map(|interface| {
+            Some(BoundInterface {
+                interface: interface.file_name()?.to_string_lossy().into_owned(),
+                driver: read_driver(interface)?,
+            })
+        })
+        .collect())
+}
+
+/// Detach the driver from an interface by writing the interface name to
+/// /sys/bus/usb/drivers/DRIVER/unbind; replugging binds it again. A driver
+/// holding several interfaces (cdc_acm's control and data) may let go of all
+/// of them at once, so one that's already free counts as done.
+pub fn unbind_driver(bound: &BoundInterface) -> std::io::Result<()> {
+    let driver_dir = Path::new("/sys/bus/usb/drivers").join(&bound.driver);
+    if fs::symlink_metadata(driver_dir.join(&bound.interface)).is_err() {
+        return Ok(());
+    }
+    fs::write(driver_dir.join("unbind"), &bound.interface)
+}
+
+/// Whole-disk block devices ("/dev/sdb") backed by a USB device, found by
+/// resolving each /sys/block entry and checking it sits under the device
+pub fn block_devices(device: &UsbDevice) -> Vec<String> {
+    let Some(usb_dir) = device
+        .locate_sysfs_dir()
+        .and_then(|dir| dir.canonicalize().ok())
+    else {
+        return vec![];
+    };
+    let Ok(entries) = fs::read_dir("/sys/block") else {
+        return vec![];
+    };
+    let mut disks: Vec<String> = entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .path()
+                .canonicalize()
+                .is_ok_and(|target| target.starts_with(&usb_dir))
+        })
+        .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+        .collect();
+    disks.sort();
+    disks
+}
+
+/// Partitions of a disk ("/dev/sdb" -> "/dev/sdb1", ...), from its sysfs entry
+fn partitions(disk: &str) -> Vec<String> {
+    let name = disk.trim_start_matches("/dev/");
+    let Ok(entries) = fs::read_dir(Path::new("/sys/block").join(name)) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().join("partition").exists())
+        .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+        .collect()
+}
+
+/// Run a udisksctl subcommand, turning a non-zero exit into an error carrying
+/// its message. Never lets polkit prompt: with the TUI up, a password prompt
+/// on the terminal would hang it.
+fn udisksctl(command: &str, args: &[&str]) -> std::io::Result<()> {
+    let output = Command::new("udisksctl")
+        .arg(command)
+        .arg("--no-user-interaction")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(std::io::Error::other(
+        if message.contains("NotAuthorized") {
+            "Not authorized (polkit refused; try as root)".to_string()
+        } else if message.is_empty() {
+            format!("udisksctl {} failed", command)
+        } else {
+            message
+        },
+    ))
+}
+
+/// Safely remove a mass storage device: unmount anything mounted from its
+/// disks, then power it off, like `udisksctl unmount` + `udisksctl power-off`.
+/// Returns how many filesystems were unmounted.
+pub fn eject_device(device: &UsbDevice) -> std::io::Result<usize> {
+    let disks = block_devices(device);
+    if disks.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no block device found",
+        ));
+    }
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let mounted: Vec<&str> = mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+
+    let mut unmounted = 0;
+    for disk in &disks {
+        let mut nodes = partitions(disk);
+        nodes.push(disk.clone());
+        for node in nodes.iter().filter(|node| mounted.contains(&node.as_str())) {
+            udisksctl("unmount", &["--block-device", node])?;
+            unmounted += 1;
+        }
+    }
+    for disk in &disks {
+        udisksctl("power-off", &["--block-device", disk])?;
+    }
+    Ok(unmounted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<UsbDevice> {
+        parse_lsusb_line(line, &HashMap::new(), &DfuRules::default())
+    }
+
+    #[test]
+    fn parses_zero_padded_lsusb_output() {
+        let device =
+            parse("Bus 001 Device 003: ID 0483:df11 STMicroelectronics STM Device in DFU Mode")
+                .unwrap();
+        assert_eq!(device.bus, "001");
+        assert_eq!(device.device, "003");
+        assert_eq!(device.id(), "0483:df11");
+        assert_eq!(device.name, "STMicroelectronics STM Device in DFU Mode");
+        assert_eq!(device.dev_path, "/dev/bus/usb/001/003");
+        assert!(device.is_dfu);
+    }
+
+    #[test]
+    fn parses_busybox_output_without_padding_or_name() {
+        let device = parse("Bus 1 Device 2: ID 1d6b:0002").unwrap();
+        assert_eq!(device.bus, "001");
+        assert_eq!(device.device, "002");
+        assert_eq!(device.id(), "1d6b:0002");
+        assert_eq!(device.name, "Unknown");
+    }
+
+    #[test]
+    fn tolerates_extra_whitespace_and_detached_colon() {
+        let device = parse("  Bus  002   Device 010 :  ID 0483:3748   ST-LINK/V2  ").unwrap();
+        assert_eq!(device.key(), "002:010");
+        assert_eq!(device.id(), "0483:3748");
+        assert_eq!(device.name, "ST-LINK/V2");
+    }
+
+    #[test]
+    fn keeps_unicode_device_names_intact() {
+        let device = parse("Bus 003 Device 004: ID 1234:ABCD Ünïcödé  Gerät 日本").unwrap();
+        assert_eq!(device.id(), "1234:abcd");
+        assert_eq!(device.name, "Ünïcödé  Gerät 日本");
+    }
+
+    #[test]
+    fn names_containing_an_id_marker_stay_in_the_name() {
+        let device =
+            parse("Bus 001 Device 007: ID 1a2b:3c4d Acme Probe: ID 0000:0000 edition").unwrap();
+        assert_eq!(device.id(), "1a2b:3c4d");
+        assert_eq!(device.name, "Acme Probe: ID 0000:0000 edition");
+
+        let device = parse("Bus 001 Device 008: ID 0483:5740 Board ID 42 Virtual COM").unwrap();
+        assert_eq!(device.id(), "0483:5740");
+        assert_eq!(device.name, "Board ID 42 Virtual COM");
+    }
+
+    #[test]
+    fn rejects_lines_that_are_not_devices() {
+        assert!(parse("").is_none());
+        assert!(parse("Bus 001 Device 002:").is_none());
+        assert!(parse("Bus 001 Device 002: ID nothex Name").is_none());
+        assert!(parse("Bus 001 Device 002: ID Widget ID 1234:5678").is_none());
+        assert!(parse("Couldn't open device, some information will be missing").is_none());
+    }
+
+    #[test]
+    fn formats_bcd_device_revisions() {
+        assert_eq!(format_bcd("0102").as_deref(), Some("1.02"));
+        assert_eq!(format_bcd("0200").as_deref(), Some("2.00"));
+        assert_eq!(format_bcd("0001").as_deref(), Some("0.01"));
+        assert_eq!(format_bcd("1234").as_deref(), Some("12.34"));
+        assert_eq!(format_bcd("102"), None);
+    }
+
+    #[test]
+    fn counts_unparseable_lines_but_not_blank_ones() {
+        let output = "Bus 001 Device 001: ID 1d6b:0002 Linux Foundation 2.0 root hub\n\
+                      \n\
+                      garbage\n\
+                      Bus 2 Device 3: ID 10c4:ea60\n";
+        let (devices, failures) = parse_lsusb_output(output, &HashMap::new(), &DfuRules::default());
+        assert_eq!(devices.len(), 2);
+        assert_eq!(failures, 1);
+    }
+
+    #[test]
+    fn parses_the_dfu_functional_descriptor() {
+        let expected = DfuDescriptor {
+            attributes: 0x0b,
+            detach_timeout_ms: 255,
+            transfer_size: 2048,
+            dfu_version: Some(0x011a),
+        };
+        // Device, configuration, a HID interface and its 0x21, then DFU's
+        let raw = [
+            &[18, 0x01][..],
+            &[0; 16],
+            &[9, 0x02, 36, 0, 2, 1, 0, 0x80, 50],
+            &[9, 0x04, 0, 0, 1, 0x03, 0, 0, 0],
+            &[9, 0x21, 0x11, 0x01, 0, 1, 0x22, 64, 0],
+            &[9, 0x04, 1, 0, 0, 0xfe, 0x01, 0x02, 0],
+            &[9, 0x21, 0x0b, 255, 0, 0x00, 0x08, 0x1a, 0x01],
+        ]
+        .concat();
+        assert_eq!(parse_dfu_descriptors(&raw), Some(expected));
+        assert_eq!(
+            expected.capabilities(),
+            ["download", "upload", "will detach"]
+        );
+        assert_eq!(expected.version().as_deref(), Some("1.1a"));
+
+        let verbose = "Interface Descriptor:\n\
+                       bInterfaceClass       254 Application Specific Interface\n\
+                       Device Firmware Upgrade Interface Descriptor:\n\
+                       bLength                             9\n\
+                       bmAttributes                       11\n\
+                       Will Detach\n\
+                       wDetachTimeout                    255 milliseconds\n\
+                       wTransferSize                    2048 bytes\n\
+                       bcdDFUVersion                   1.1a\n";
+        assert_eq!(parse_dfu_lsusb(verbose), Some(expected));
+        assert_eq!(parse_dfu_lsusb("Device Descriptor:\n"), None);
+    }
+
+    #[test]
+    fn poller_stops_within_the_poll_interval() {
+        let interval = Duration::from_secs(5);
+        let lsusb = LsusbOptions {
+            path: Some(PathBuf::from("/nonexistent/lsusb")),
+            ..LsusbOptions::default()
+        };
+        let (mut poller, polls) = Poller::spawn(
+            Backend::Lsusb,
+            UsbIds::new(),
+            DfuRules::default(),
+            lsusb,
+            interval,
+        );
+        poller.refresh();
+        polls.recv_timeout(interval).expect("triggered poll");
+
+        let stopping = Instant::now();
+        poller.stop();
+        assert!(stopping.elapsed() < interval);
+        // The thread is gone, so its end of the channel is too
+        assert!(polls.recv().is_err());
+    }
+
+    #[test]
+    fn poller_coalesces_a_burst_of_refreshes() {
+        let lsusb = LsusbOptions {
+            path: Some(PathBuf::from("/nonexistent/lsusb")),
+            ..LsusbOptions::default()
+        };
+        let (mut poller, polls) = Poller::spawn(
+            Backend::Lsusb,
+            UsbIds::new(),
+            DfuRules::default(),
+            lsusb,
+            Duration::from_secs(5),
+        );
+        for _ in 0..20 {
+            poller.refresh();
+        }
+        thread::sleep(MIN_SCAN_GAP * 3);
+        poller.stop();
+
+        let starts: Vec<Instant> = polls.try_iter().map(|(_, _, start)| start).collect();
+        let counts = poller.trigger_counts();
+        // Every trigger either started a scan or was folded into one
+        assert_eq!(starts.len() as u64 + counts.coalesced, 20);
+        assert!(starts.len() < 20);
+        for pair in starts.windows(2) {
+            assert!(pair[1].duration_since(pair[0]) >= MIN_SCAN_GAP);
+        }
+    }
+
+    #[test]
+    fn poller_rescans_after_a_trigger_mid_scan() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script =
+            std::env::temp_dir().join(format!("cursed-usb-slow-lsusb-{}", std::process::id()));
+        fs::write(
+            &script,
+            "#!/bin/sh\nsleep 0.3\necho 'Bus 001 Device 001: ID 1d6b:0002 root hub'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let lsusb = LsusbOptions {
+            path: Some(script.clone()),
+            skip_tty_scan: true,
+            ..LsusbOptions::default()
+        };
+        let (mut poller, polls) = Poller::spawn(
+            Backend::Lsusb,
+            UsbIds::new(),
+            DfuRules::default(),
+            lsusb,
+            Duration::from_secs(5),
+        );
+        poller.refresh();
+        while poller.state.phase.load(Ordering::SeqCst) != SCANNING {
+            thread::sleep(Duration::from_millis(5));
+        }
+        // The device that prompted these may have arrived after lsusb looked
+        poller.refresh();
+        poller.refresh();
+        let first = polls.recv_timeout(Duration::from_secs(2));
+        let follow_up = polls.recv_timeout(Duration::from_secs(2));
+        poller.stop();
+        let _ = fs::remove_file(&script);
+
+        assert!(first.is_ok());
+        assert!(follow_up.is_ok());
+        assert!(polls.try_recv().is_err());
+        assert_eq!(poller.trigger_counts().coalesced, 1);
+    }
+
+    #[test]
+    fn poller_backs_off_while_idle_and_snaps_back_on_a_change() {
+        let file =
+            std::env::temp_dir().join(format!("cursed-usb-backoff-{}.txt", std::process::id()));
+        fs::write(&file, "Bus 001 Device 001: ID 1d6b:0002 root hub\n").unwrap();
+        let interval = Duration::from_millis(20);
+        let lsusb = LsusbOptions {
+            file: Some(file.clone()),
+            skip_tty_scan: true,
+            ..LsusbOptions::default()
+        };
+        let (mut poller, polls) = Poller::spawn(
+            Backend::Lsusb,
+            UsbIds::new(),
+            DfuRules::default(),
+            lsusb,
+            interval,
+        );
+        for _ in 0..IDLE_SCANS_BEFORE_BACKOFF + 2 {
+            polls.recv_timeout(MAX_IDLE_INTERVAL).expect("idle poll");
+        }
+        assert!(poller.current_interval() > interval);
+
+        fs::write(
+            &file,
+            "Bus 001 Device 001: ID 1d6b:0002 root hub\n\
+             Bus 001 Device 005: ID 10c4:ea60 CP210x\n",
+        )
+        .unwrap();
+        poller.refresh();
+        // The interval is stored before the poll is sent, so it's current here
+        while polls
+            .recv_timeout(MAX_IDLE_INTERVAL)
+            .expect("poll")
+            .0
+            .devices
+            .len()
+            != 2
+        {}
+        poller.stop();
+        let _ = fs::remove_file(&file);
+
+        assert_eq!(poller.current_interval(), interval);
+    }
+
+    #[test]
+    fn replays_a_captured_listing_without_hardware() {
+        let file = std::env::temp_dir().join(format!("cursed-usb-test-{}.txt", std::process::id()));
+        fs::write(
+            &file,
+            "Bus 002 Device 001: ID 1d6b:0003 Linux Foundation 3.0 root hub\n\
+             Bus 001 Device 005: ID 10c4:ea60 Silicon Labs CP210x UART Bridge\n\
+             not a device line\n",
+        )
+        .unwrap();
+        let lsusb = LsusbOptions {
+            file: Some(file.clone()),
+            skip_tty_scan: true,
+            ..LsusbOptions::default()
+        };
+        // The sysfs backend is overridden by the file
+        let poll = get_usb_devices(Backend::Sysfs, &UsbIds::new(), &DfuRules::default(), &lsusb);
+        let _ = fs::remove_file(&file);
+
+        let ids: Vec<String> = poll.devices.iter().map(|d| d.id()).collect();
+        assert_eq!(ids, ["1d6b:0003", "10c4:ea60"]);
+        assert_eq!(poll.parse_failures, 1);
+        assert!(poll.warnings.is_empty());
+        assert!(poll.devices.iter().all(|d| d.tty_paths.is_empty()));
+        assert!(lsusb.run(&["-v".into()]).is_err());
+    }
+
+    /// A dump as `--once --format json` wrote it at schema version 1
+    const EXPORT_V1: &str = r#"{
+  "schema_version": 1,
+  "generated_at": "2026-10-14T09:30:00.000+02:00",
+  "devices": [
+    {
+      "bus": "001",
+      "device": "004",
+      "vendor_id": "0483",
+      "product_id": "3748",
+      "name": "STMicroelectronics ST-LINK/V2",
+      "vendor_name": "STMicroelectronics",
+      "product_name": "ST-LINK/V2",
+      "manufacturer": "STMicroelectronics",
+      "serial": "0669FF555052836687031947",
+      "fw_version": "1.00",
+      "is_dfu": false,
+      "speed": "full",
+      "drivers": ["cdc_acm"],
+      "class_code": 2,
+      "subclass_code": 2,
+      "interface_classes": [2, 10],
+      "max_power_ma": 100,
+      "port_path": [2],
+      "endpoint_count": 3,
+      "endpoint_kinds": ["IN interrupt", "IN bulk", "OUT bulk"],
+      "urb_count": 12,
+      "authorized": true,
+      "runtime_status": "active",
+      "diagnostics": [],
+      "dev_path": "/dev/bus/usb/001/004",
+      "tty_paths": ["/dev/ttyACM0", "/dev/ttyACM1"],
+      "id": "0483:3748",
+      "display_path": "/dev/ttyACM0"
+    }
+  ]
+}"#;
+
+    #[test]
+    fn reads_schema_v1_fixture() {
+        let export: Export<UsbDevice> = serde_json::from_str(EXPORT_V1).unwrap();
+        assert_eq!(export.schema_version, 1);
+        let device = &export.devices[0];
+        assert_eq!(device.id(), "0483:3748");
+        assert_eq!(device.speed, Some(UsbSpeed::Full));
+        assert_eq!(device.tty_paths, ["/dev/ttyACM0", "/dev/ttyACM1"]);
+        assert_eq!(device.display_path(), "/dev/ttyACM0");
+    }
+}
@@ -1,184 +1,594 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::process::Command;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use base64::Engine;
+use chrono::{DateTime, Local, SecondsFormat};
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::{eyre::WrapErr, Result};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+    MouseEvent, MouseEventKind,
+};
+use cursed_usb::{
+    bound_interfaces, class_label, descriptor_lines, dfu_descriptor, eject_device, get_usb_devices,
+    hub_ports, hub_power, hub_power_switching, load_usb_ids, reset_device, set_authorized,
+    unbind_driver, Backend, BoundInterface, DfuDescriptor, DfuRules, Enumeration, Export, HubPower,
+    LsusbOptions, PollResult, Poller, PowerSwitching, TriggerCounts, UsbDevice, UsbSpeed,
+    SCHEMA_VERSION,
+};
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Sparkline, Wrap,
+    },
     DefaultTerminal, Frame,
 };
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, EnvFilter};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct UsbDevice {
-    bus: String,
-    device: String,
-    vendor_id: String,
-    product_id: String,
-    name: String,
-    is_dfu: bool,
-    dev_path: String,       // /dev/bus/usb/BUS/DEVICE or tty path
-    tty_path: Option<String>, // /dev/ttyUSB0, /dev/ttyACM0, etc.
+/// A clean TUI for viewing USB devices
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Background poll interval in milliseconds
+    #[arg(long, default_value_t = 200, value_parser = clap::value_parser!(u64).range(20..=10000))]
+    interval_ms: u64,
+
+    /// Don't load or save cumulative stats in ~/.local/state/cursed-usb
+    #[arg(long)]
+    no_persist: bool,
+
+    /// Skip the TUI and print one JSON object per line whenever the device set changes
+    #[arg(long)]
+    watch_json: bool,
+
+    /// Pop up a desktop notification when a DFU device appears
+    #[arg(long)]
+    notify: bool,
+
+    /// Run this shell command whenever a tracked device connects. {tty}, {vid},
+    /// {pid}, {serial}, {bus}, {dev} and {name} are filled in, already quoted.
+    #[arg(long, value_name = "CMD")]
+    on_connect: Option<String>,
+
+    /// Like --on-connect, when a tracked device disconnects
+    #[arg(long, value_name = "CMD")]
+    on_disconnect: Option<String>,
+
+    /// Skip the TUI, wait for a device and print its path: VID:PID, "dfu", or a tty glob like /dev/ttyACM*
+    #[arg(long, value_name = "TARGET", value_parser = WaitTarget::parse, conflicts_with = "watch_json")]
+    wait_for: Option<WaitTarget>,
+
+    /// Give up on --wait-for after this many seconds and exit with status 1
+    #[arg(long, value_name = "SECS", requires = "wait_for")]
+    timeout: Option<u64>,
+
+    /// Skip the TUI, print the current devices once and exit
+    #[arg(long, conflicts_with_all = ["watch_json", "wait_for"])]
+    once: bool,
+
+    /// Only track devices on this bus, in the TUI and every headless mode
+    #[arg(long, value_name = "N")]
+    bus: Option<u32>,
+
+    /// Select the device that owns this tty (e.g. /dev/ttyUSB0) and keep it
+    /// selected when it re-enumerates under a new bus/device number
+    #[arg(long, value_name = "TTY", value_parser = parse_tty)]
+    highlight: Option<String>,
+
+    /// Only track devices with this vendor ID; repeat for several
+    #[arg(long, value_name = "VID", value_parser = parse_hex_id)]
+    filter_vid: Vec<String>,
+
+    /// Only track devices with this product ID; repeat for several. ANDs with --filter-vid
+    #[arg(long, value_name = "PID", value_parser = parse_hex_id)]
+    filter_pid: Vec<String>,
+
+    /// Color palette, overriding `theme` in the config
+    #[arg(long, value_enum)]
+    theme: Option<ThemeName>,
+
+    /// Plain text with no colors, as when NO_COLOR is set
+    #[arg(long)]
+    no_color: bool,
+
+    /// Where device information comes from: sysfs, lsusb, or rusb (libusb, when
+    /// built with `--features rusb`). Defaults to sysfs, or lsusb without it.
+    #[arg(long, value_name = "BACKEND", value_parser = Backend::from_name, conflicts_with = "lsusb_file")]
+    backend: Option<Backend>,
+
+    /// lsusb binary to run, overriding `lsusb_path` in the config
+    #[arg(long, value_name = "PATH")]
+    lsusb_path: Option<PathBuf>,
+
+    /// Run lsusb as `sudo -n lsusb`; fails with a message instead of prompting for a password
+    #[arg(long)]
+    lsusb_sudo: bool,
+
+    /// Parse this saved `lsusb` output on every poll instead of running lsusb, to replay a report
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["lsusb_path", "lsusb_sudo"])]
+    lsusb_file: Option<PathBuf>,
+
+    /// Don't look for ttys, so nothing depends on this machine's /dev and /sys
+    #[arg(long)]
+    no_tty_scan: bool,
+
+    /// Skip the TUI and append timestamped connect/disconnect/flapping events to this file
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["watch_json", "wait_for", "once"])]
+    log_file: Option<PathBuf>,
+
+    /// Time N enumerations with each available backend, print the latency distribution and exit
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["watch_json", "wait_for", "once", "log_file"]
+    )]
+    bench: Option<u32>,
+
+    /// Emit structured events at this level and up, overriding RUST_LOG: JSON
+    /// on stderr in headless modes, appended to cursed-usb.log in the state dir from the TUI
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    log_level: Option<LogLevel>,
+
+    /// Output format for --once
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, requires = "once")]
+    format: OutputFormat,
+
+    #[command(subcommand)]
+    command: Option<DeviceCommand>,
+}
+
+/// Act on devices by VID:PID without the TUI
+#[derive(Debug, Subcommand)]
+enum DeviceCommand {
+    /// USB-reset every device with this VID:PID
+    Reset {
+        #[arg(value_parser = parse_usb_id)]
+        id: String,
+    },
+    /// Print the tty of every device with this VID:PID
+    Tty {
+        #[arg(value_parser = parse_usb_id)]
+        id: String,
+    },
+}
+
+/// "0483:DF11" -> "0483:df11"; anything but two 4-digit hex halves is refused
+fn parse_usb_id(value: &str) -> std::result::Result<String, String> {
+    let is_id = value.split_once(':').is_some_and(|(vid, pid)| {
+        [vid, pid]
+            .iter()
+            .all(|part| part.len() == 4 && part.chars().all(|c| c.is_ascii_hexdigit()))
+    });
+    if is_id {
+        Ok(value.to_lowercase())
+    } else {
+        Err("expected a VID:PID like 0483:df11".to_string())
+    }
+}
+
+/// "ttyUSB0" -> "/dev/ttyUSB0"; symlinks such as /dev/serial/by-id/... are
+/// resolved so they match the kernel name
+fn parse_tty(value: &str) -> std::result::Result<String, String> {
+    let path = if value.starts_with('/') {
+        PathBuf::from(value)
+    } else {
+        Path::new("/dev").join(value)
+    };
+    let path = fs::canonicalize(&path).unwrap_or(path);
+    Ok(path.to_string_lossy().into_owned())
 }
 
-impl UsbDevice {
-    /// Unique key for this specific device (bus + device number)
-    fn key(&self) -> String {
-        format!("{}:{}", self.bus, self.device)
+/// "DF11" -> "df11"; one 4-digit hex half of a VID:PID
+fn parse_hex_id(value: &str) -> std::result::Result<String, String> {
+    if value.len() == 4 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(value.to_lowercase())
+    } else {
+        Err("expected 4 hex digits like 0483".to_string())
     }
+}
 
-    fn id(&self) -> String {
-        format!("{}:{}", self.vendor_id, self.product_id)
+/// Fill a hook template in from a device, in one pass so a value that looks
+/// like a placeholder stays as it is. Each value is single-quoted for `sh -c`:
+/// names and serials come from the device and mustn't be able to run anything.
+/// Anything else in braces, like `${HOME}`, is left to the shell.
+fn expand_hook(template: &str, device: &UsbDevice) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let placeholder = tail
+            .find('}')
+            .and_then(|end| Some((end, hook_value(&tail[1..end], device)?)));
+        match placeholder {
+            Some((end, value)) => {
+                expanded.push_str(&shell_quote(&value));
+                rest = &tail[end + 1..];
+            }
+            None => {
+                expanded.push('{');
+                rest = &tail[1..];
+            }
+        }
     }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// A hook placeholder's value, empty when the device doesn't have one
+fn hook_value(placeholder: &str, device: &UsbDevice) -> Option<String> {
+    let value = match placeholder {
+        "tty" => device.tty_path().unwrap_or_default().to_string(),
+        "vid" => device.vendor_id.clone(),
+        "pid" => device.product_id.clone(),
+        "serial" => device.serial.clone().unwrap_or_default(),
+        "bus" => device.bus.clone(),
+        "dev" => device.device.clone(),
+        "name" => device.name.clone(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// 'it'\''s' for `it's`: safe to paste into a shell command as one word
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
 
-    /// Display path - prefer tty over bus path
-    fn display_path(&self) -> &str {
-        self.tty_path.as_deref().unwrap_or(&self.dev_path)
+/// --log-level values, most to least severe
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn directive(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
     }
 }
 
-/// Build a map of (bus, devnum) -> tty device path by scanning /dev/serial/by-path
-/// This is fast because it just reads symlinks
-fn get_tty_map() -> HashMap<(u32, u32), String> {
-    let mut map = HashMap::new();
+/// How `--once` prints the device list
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
 
-    // Method 1: Check /dev/serial/by-id (fastest, has nice names)
-    if let Ok(entries) = fs::read_dir("/dev/serial/by-id") {
-        for entry in entries.flatten() {
-            if let Ok(target) = fs::read_link(entry.path()) {
-                let target_str = target.to_string_lossy();
-                // Extract ttyUSB0 or ttyACM0 from the target
-                if let Some(tty_name) = target_str.strip_prefix("../../") {
-                    if tty_name.starts_with("ttyUSB") || tty_name.starts_with("ttyACM") {
-                        // Now find which bus/dev this corresponds to
-                        if let Some((bus, dev)) = get_tty_bus_dev(tty_name) {
-                            map.insert((bus, dev), format!("/dev/{}", tty_name));
-                        }
-                    }
-                }
-            }
+/// What `--wait-for` is waiting for
+#[derive(Debug, Clone)]
+enum WaitTarget {
+    Id(String),
+    Dfu,
+    Tty(glob::Pattern),
+}
+
+impl WaitTarget {
+    fn parse(value: &str) -> std::result::Result<Self, String> {
+        if value.eq_ignore_ascii_case("dfu") {
+            return Ok(WaitTarget::Dfu);
         }
+        if let Ok(id) = parse_usb_id(value) {
+            return Ok(WaitTarget::Id(id));
+        }
+        glob::Pattern::new(value)
+            .map(WaitTarget::Tty)
+            .map_err(|err| format!("not a VID:PID, \"dfu\" or a valid tty glob: {}", err))
     }
 
-    // Method 2: Direct scan of /dev/ttyUSB* and /dev/ttyACM*
-    for prefix in &["ttyUSB", "ttyACM"] {
-        for i in 0..16 {
-            let tty_name = format!("{}{}", prefix, i);
-            if let Some((bus, dev)) = get_tty_bus_dev(&tty_name) {
-                map.entry((bus, dev)).or_insert_with(|| format!("/dev/{}", tty_name));
-            }
+    /// The path to print for a matching device
+    fn matched_path(&self, device: &UsbDevice) -> Option<String> {
+        match self {
+            WaitTarget::Id(id) => (device.id() == *id).then(|| device.dev_path.clone()),
+            WaitTarget::Dfu => device.is_dfu.then(|| device.dev_path.clone()),
+            // The tty is the thing being waited for, so that's what gets printed
+            WaitTarget::Tty(pattern) => device
+                .tty_paths
+                .iter()
+                .find(|tty| pattern.matches(tty))
+                .cloned(),
         }
     }
+}
 
-    map
+/// A device as written to JSON dumps, with the derived fields included
+#[derive(Serialize)]
+struct DeviceExport<'a> {
+    #[serde(flatten)]
+    device: &'a UsbDevice,
+    id: String,
+    display_path: &'a str,
 }
 
-/// Get bus and device number for a tty device by reading sysfs
-fn get_tty_bus_dev(tty_name: &str) -> Option<(u32, u32)> {
-    // Read /sys/class/tty/ttyUSB0/device/../.. to find the USB device
-    let device_path = format!("/sys/class/tty/{}/device", tty_name);
-    
-    // Follow symlinks to find the USB device directory
-    let real_path = fs::canonicalize(&device_path).ok()?;
-    
-    // Walk up to find busnum/devnum
-    let mut current = real_path.as_path();
-    for _ in 0..5 {
-        current = current.parent()?;
-        let busnum_path = current.join("busnum");
-        let devnum_path = current.join("devnum");
-        
-        if busnum_path.exists() && devnum_path.exists() {
-            let bus: u32 = fs::read_to_string(&busnum_path).ok()?.trim().parse().ok()?;
-            let dev: u32 = fs::read_to_string(&devnum_path).ok()?.trim().parse().ok()?;
-            return Some((bus, dev));
+impl<'a> From<&'a UsbDevice> for DeviceExport<'a> {
+    fn from(device: &'a UsbDevice) -> Self {
+        Self {
+            device,
+            id: device.id(),
+            display_path: device.display_path(),
         }
     }
-    
-    None
 }
 
-fn get_usb_devices() -> Vec<UsbDevice> {
-    let output = Command::new("lsusb").output();
-    let tty_map = get_tty_map();
+/// The devices as a versioned JSON document: `{ "schema_version",
+/// "generated_at", "devices": [...] }`. Renaming or removing a `UsbDevice`
+/// or `DeviceExport` field changes what scripts read, so it must come with
+/// a `SCHEMA_VERSION` bump and a fixture test for the new version.
+fn devices_json(devices: &[UsbDevice]) -> Result<String> {
+    let export = Export::new(
+        Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        devices.iter().map(DeviceExport::from).collect(),
+    );
+    Ok(serde_json::to_string_pretty(&export)?)
+}
+
+/// Write the devices as JSON to cursed-usb-dump-<timestamp>.json in the current directory
+fn export_json(devices: &[UsbDevice]) -> Result<String> {
+    let path = format!(
+        "cursed-usb-dump-{}.json",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    fs::write(&path, devices_json(devices)?)?;
+    Ok(path)
+}
+
+/// Write the devices to cursed-usb-<timestamp>.csv in the current directory
+fn export_csv(devices: &[UsbDevice]) -> Result<String> {
+    let path = format!("cursed-usb-{}.csv", Local::now().format("%Y%m%d-%H%M%S"));
+    fs::write(&path, devices_csv(devices))?;
+    Ok(path)
+}
+
+/// Header plus one row per device, the layout both 'x' and `--once --format csv` write
+fn devices_csv(devices: &[UsbDevice]) -> String {
+    let mut out = String::from("name,vendor_id,product_id,bus,device,tty_paths,is_dfu,serial\n");
+    for device in devices {
+        let row = [
+            device.name.as_str(),
+            &device.vendor_id,
+            &device.product_id,
+            &device.bus,
+            &device.device,
+            &device.tty_paths.join(" "),
+            if device.is_dfu { "true" } else { "false" },
+            device.serial.as_deref().unwrap_or_default(),
+        ];
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Settings from ~/.config/cursed-usb/config.toml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct Config {
+    #[serde(flatten)]
+    dfu: DfuRules,
+    /// A device that appears/disappears more than this many times...
+    flap_threshold: usize,
+    /// ...within this many seconds is marked as flapping
+    flap_window_secs: u64,
+    /// How long a disconnected device stays in the 'u' departed list
+    departed_retention_secs: u64,
+    /// Content layout, cycled with 'v' and saved back here
+    layout: ViewLayout,
+    /// Device list width in the split layout, in percent; adjusted with '<'/'>' and saved back here
+    split_pct: u16,
+    /// `[keys]` table: action name -> key or list of keys, replacing that action's defaults
+    keys: HashMap<String, KeyNames>,
+    /// Color palette, unless --theme overrides it
+    theme: ThemeName,
+    /// lsusb binary to run instead of the one on PATH, unless --lsusb-path overrides it
+    lsusb_path: Option<PathBuf>,
+    /// Run lsusb through `sudo -n`, same as --lsusb-sudo
+    lsusb_sudo: bool,
+    /// Hide root hubs and `ignore` matches from the list, toggled with 'H' and saved back here
+    hide_internal: bool,
+    /// VID:PID patterns for internal gear to hide along with the root hubs, e.g. "8087:*"
+    ignore: Vec<IdPattern>,
+}
+
+/// A VID:PID glob from the config file; `*` stands for either half
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+struct IdPattern(glob::Pattern);
 
-    match output {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            stdout
-                .lines()
-                .filter_map(|line| parse_lsusb_line(line, &tty_map))
-                .collect()
+impl TryFrom<String> for IdPattern {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        if !value.contains(':') {
+            return Err(format!("expected VID:PID, got \"{}\"", value));
         }
-        Err(_) => vec![],
+        glob::Pattern::new(&value.to_lowercase())
+            .map(IdPattern)
+            .map_err(|err| format!("bad pattern \"{}\": {}", value, err))
     }
 }
 
-fn parse_lsusb_line(line: &str, tty_map: &HashMap<(u32, u32), String>) -> Option<UsbDevice> {
-    // Parse: Bus 001 Device 002: ID 1234:5678 Device Name
-    let parts: Vec<&str> = line.splitn(2, ": ID ").collect();
-    if parts.len() != 2 {
-        return None;
+impl IdPattern {
+    fn matches(&self, device: &UsbDevice) -> bool {
+        self.0.matches(&device.id().to_lowercase())
     }
+}
 
-    let prefix = parts[0];
-    let suffix = parts[1];
+/// One key name or several, so both `quit = "q"` and `next = ["j", "down"]` work
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum KeyNames {
+    One(String),
+    Many(Vec<String>),
+}
 
-    // Parse bus and device from prefix
-    let prefix_parts: Vec<&str> = prefix.split_whitespace().collect();
-    if prefix_parts.len() < 4 {
-        return None;
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dfu: DfuRules::default(),
+            flap_threshold: 4,
+            flap_window_secs: 10,
+            departed_retention_secs: 900,
+            layout: ViewLayout::default(),
+            split_pct: 55,
+            keys: HashMap::new(),
+            theme: ThemeName::default(),
+            lsusb_path: None,
+            lsusb_sudo: false,
+            hide_internal: false,
+            ignore: vec![],
+        }
     }
+}
 
-    let bus = prefix_parts[1].to_string();
-    let device = prefix_parts[3].to_string();
+impl Config {
+    fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("cursed-usb").join("config.toml"))
+    }
 
-    // Parse ID and name from suffix
-    let id_and_name: Vec<&str> = suffix.splitn(2, ' ').collect();
-    let id = id_and_name[0];
-    let name = if id_and_name.len() > 1 {
-        id_and_name[1].to_string()
-    } else {
-        "Unknown".to_string()
-    };
+    /// Load the config file. A missing file gives defaults; a broken one
+    /// gives defaults plus a warning to show the user.
+    fn load() -> (Self, Option<String>) {
+        let Some(path) = Self::path() else {
+            return (Self::default(), None);
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return (Self::default(), None)
+            }
+            Err(err) => {
+                return (
+                    Self::default(),
+                    Some(format!("couldn't read {}: {}", path.display(), err)),
+                )
+            }
+        };
+        match toml::from_str(&contents) {
+            Ok(config) => (config, None),
+            Err(err) => (
+                Self::default(),
+                Some(format!(
+                    "config error in {}: {}",
+                    path.display(),
+                    err.message()
+                )),
+            ),
+        }
+    }
 
-    let id_parts: Vec<&str> = id.split(':').collect();
-    if id_parts.len() != 2 {
-        return None;
+    /// Write one top-level key into the config file, keeping every other key as is
+    fn save_key(key: &str, value: impl Serialize) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        // Edit the document in place so the user's comments and key order survive
+        let mut doc = match fs::read_to_string(&path) {
+            Ok(contents) => contents.parse::<toml_edit::DocumentMut>()?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => toml_edit::DocumentMut::new(),
+            Err(err) => return Err(err.into()),
+        };
+        doc[key] = toml_edit::value(value.serialize(toml_edit::ser::ValueSerializer::new())?);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, doc.to_string())?;
+        Ok(())
     }
+}
+
+/// Format a duration as HH:MM:SS
+fn format_hms(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
 
-    let vendor_id = id_parts[0].to_string();
-    let product_id = id_parts[1].to_string();
+/// The cumulative part of `Stats` that carries over between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct PersistedStats {
+    devices_ever_seen: BTreeSet<String>,
+    dfu_devices_ever_seen: BTreeSet<String>,
+    connects: u64,
+    disconnects: u64,
+}
 
-    let name_lower = name.to_lowercase();
-    let is_dfu = name_lower.contains("dfu")
-        || name_lower.contains("download")
-        || name_lower.contains("boot");
+impl PersistedStats {
+    fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state"))
+            })?;
+        Some(base.join("cursed-usb").join("stats.json"))
+    }
 
-    // Build /dev/bus/usb path
-    let dev_path = format!("/dev/bus/usb/{}/{}", bus, device);
+    /// Missing or unreadable state just starts from zero
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
 
-    // Look up tty path
-    let bus_num: u32 = bus.parse().unwrap_or(0);
-    let dev_num: u32 = device.parse().unwrap_or(0);
-    let tty_path = tty_map.get(&(bus_num, dev_num)).cloned();
+    fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
 
-    Some(UsbDevice {
-        bus,
-        device,
-        vendor_id,
-        product_id,
-        name,
-        is_dfu,
-        dev_path,
-        tty_path,
-    })
+    fn remove() -> Result<()> {
+        match Self::path().map(fs::remove_file) {
+            Some(Err(err)) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
 }
 
 // Stats tracking
@@ -188,25 +598,129 @@ struct Stats {
     devices_ever_seen: HashSet<String>,
     dfu_devices_ever_seen: HashSet<String>,
     last_refresh_duration: Duration,
+    end_to_end_latency: Duration, // refresh() call (or timed poll start) to update applied
     peak_devices: usize,
     connects: u64,
     disconnects: u64,
+    parse_failures: usize,        // Unparseable lsusb lines in the latest poll
+    warning_count: u64,           // Enumeration warnings this session, repeats included
+    last_warning: Option<String>, // Newest warning from the latest poll; None once a poll is clean
+    count_history: VecDeque<(Instant, usize)>, // Device count at each refresh within HISTORY_WINDOW, newest last
+    triggers: TriggerCounts, // Refresh triggers the poller folded into other scans
+    effective_interval: Duration, // The poller's gap between scans, stretched while idle
+    connected_at: HashMap<String, Instant>, // Device key -> when its connect was seen
+    lifetime_total: Duration, // Connect-to-disconnect time summed over...
+    lifetimes: u64,          // ...this many disconnects
+    reconnects_by_identity: HashMap<String, (String, u64)>, // Identity -> name, returns after a disconnect
+    left_identities: HashSet<String>, // Identities seen disconnecting, so their next connect is a return
+    last_change: Option<Instant>,     // Newest connect or disconnect
 }
 
+/// How far back the device count sparkline reaches
+const HISTORY_WINDOW: Duration = Duration::from_secs(120);
+
 impl Stats {
-    fn new() -> Self {
+    /// Fresh session stats, seeded with the cumulative counts from earlier runs when `persist` is set
+    fn new(persist: bool, poll_interval: Duration) -> Self {
+        let saved = if persist {
+            PersistedStats::load()
+        } else {
+            PersistedStats::default()
+        };
         Self {
             start_time: Instant::now(),
             refresh_count: 0,
-            devices_ever_seen: HashSet::new(),
-            dfu_devices_ever_seen: HashSet::new(),
+            devices_ever_seen: saved.devices_ever_seen.into_iter().collect(),
+            dfu_devices_ever_seen: saved.dfu_devices_ever_seen.into_iter().collect(),
             last_refresh_duration: Duration::ZERO,
+            end_to_end_latency: Duration::ZERO,
             peak_devices: 0,
-            connects: 0,
-            disconnects: 0,
+            connects: saved.connects,
+            disconnects: saved.disconnects,
+            parse_failures: 0,
+            warning_count: 0,
+            last_warning: None,
+            count_history: VecDeque::new(),
+            triggers: TriggerCounts::default(),
+            effective_interval: poll_interval,
+            connected_at: HashMap::new(),
+            lifetime_total: Duration::ZERO,
+            lifetimes: 0,
+            reconnects_by_identity: HashMap::new(),
+            left_identities: HashSet::new(),
+            last_change: None,
+        }
+    }
+
+    /// Feed one poll's connects and disconnects into the derived metrics.
+    /// A lifetime only counts when its connect was seen too, so devices
+    /// present at startup don't drag the average down.
+    fn record_events(&mut self, arrived: &[UsbDevice], gone: &[UsbDevice], now: Instant) {
+        for device in gone {
+            if let Some(connected) = self.connected_at.remove(&device.key()) {
+                self.lifetime_total += now.duration_since(connected);
+                self.lifetimes += 1;
+            }
+            self.left_identities.insert(device.identity());
+        }
+        for device in arrived {
+            self.connected_at.insert(device.key(), now);
+            if self.left_identities.contains(&device.identity()) {
+                let entry = self
+                    .reconnects_by_identity
+                    .entry(device.identity())
+                    .or_insert_with(|| (device.name.clone(), 0));
+                entry.1 += 1;
+            }
+        }
+        if !arrived.is_empty() || !gone.is_empty() {
+            self.last_change = Some(now);
+        }
+    }
+
+    /// Mean time between a device's connect and its disconnect
+    fn average_lifetime(&self) -> Option<Duration> {
+        (self.lifetimes > 0).then(|| self.lifetime_total / self.lifetimes as u32)
+    }
+
+    /// The device that came back most often, once anything has reconnected
+    fn most_reconnected(&self) -> Option<(&str, u64)> {
+        self.reconnects_by_identity
+            .values()
+            .max_by_key(|(_, reconnects)| *reconnects)
+            .map(|(name, reconnects)| (name.as_str(), *reconnects))
+    }
+
+    /// Drop samples by age rather than count: the poller backs off while
+    /// idle, so a fixed number of them would cover a changing span of time
+    fn record_count(&mut self, count: usize, now: Instant) {
+        while self
+            .count_history
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > HISTORY_WINDOW)
+        {
+            self.count_history.pop_front();
+        }
+        self.count_history.push_back((now, count));
+    }
+
+    fn to_persisted(&self) -> PersistedStats {
+        PersistedStats {
+            devices_ever_seen: self.devices_ever_seen.iter().cloned().collect(),
+            dfu_devices_ever_seen: self.dfu_devices_ever_seen.iter().cloned().collect(),
+            connects: self.connects,
+            disconnects: self.disconnects,
         }
     }
 
+    /// Forget the cumulative counts, keeping the session timer running
+    fn clear_cumulative(&mut self) {
+        self.devices_ever_seen.clear();
+        self.dfu_devices_ever_seen.clear();
+        self.connects = 0;
+        self.disconnects = 0;
+    }
+
     fn uptime(&self) -> Duration {
         self.start_time.elapsed()
     }
@@ -233,326 +747,4668 @@ impl Stats {
     }
 }
 
-struct App {
-    devices: Vec<UsbDevice>,
-    list_state: ListState,
-    selected_key: Option<String>, // Track selection by device key, not index
-    should_quit: bool,
-    stats: Stats,
-    device_receiver: Receiver<(Vec<UsbDevice>, Duration)>,
-    refresh_trigger: Sender<()>,
+/// Maximum number of connect/disconnect events kept in the log
+const LOG_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogKind {
+    Connect,
+    Disconnect,
 }
 
-impl App {
-    fn new() -> Self {
-        let (device_tx, device_rx) = mpsc::channel();
-        let (trigger_tx, trigger_rx) = mpsc::channel::<()>();
+/// A single connect/disconnect event
+struct LogEntry {
+    timestamp: DateTime<Local>,
+    at: Instant, // Monotonic, for the gap since the previous event
+    key: String,
+    name: String,
+    kind: LogKind,
+}
 
-        // Spawn background thread for USB polling
-        thread::spawn(move || {
-            loop {
-                // Wait for trigger or timeout (5Hz = 200ms)
-                let _ = trigger_rx.recv_timeout(Duration::from_millis(200));
+/// Built-in palettes, picked with --theme or `theme` in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
 
-                let start = Instant::now();
-                let devices = get_usb_devices();
-                let duration = start.elapsed();
+/// Every color the UI draws with, by what it means rather than what it is
+#[derive(Debug, Clone, Copy)]
+struct Theme {
+    text: Color,         // Emphasised body text (the search query)
+    muted: Color,        // Labels, hints and other secondary text
+    accent: Color,       // Titles, IDs, key names
+    border: Color,       // Panel borders
+    good: Color,         // Connects, healthy values
+    warn: Color,         // DFU devices, warnings, the filter
+    bad: Color,          // Disconnects, errors, flapping
+    dfu: Color,          // DFU count badge
+    badge_text: Color,   // Text on filled badges
+    selection: Color,    // Background of the cursor row
+    arrival: [Color; 4], // Just-arrived row background, fading step by step
+    vendors: [Color; 8], // Vendor tints; none clash with warn or bad
+}
 
-                if device_tx.send((devices, duration)).is_err() {
-                    break; // Main thread closed, exit
-                }
-            }
-        });
+impl Theme {
+    fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Self {
+                text: Color::White,
+                muted: Color::DarkGray,
+                accent: Color::Cyan,
+                border: Color::Blue,
+                good: Color::Green,
+                warn: Color::Yellow,
+                bad: Color::Red,
+                dfu: Color::Magenta,
+                badge_text: Color::Black,
+                selection: Color::DarkGray,
+                arrival: [120, 90, 60, 30].map(|green| Color::Rgb(0, green, 0)),
+                vendors: [
+                    Color::Cyan,
+                    Color::Magenta,
+                    Color::Green,
+                    Color::LightBlue,
+                    Color::LightRed,
+                    Color::LightGreen,
+                    Color::LightMagenta,
+                    Color::LightCyan,
+                ],
+            },
+            // Yellow and the light variants wash out on white, so darker stand-ins
+            ThemeName::Light => Self {
+                text: Color::Black,
+                muted: Color::Rgb(110, 110, 110),
+                accent: Color::Rgb(0, 110, 150),
+                border: Color::Blue,
+                good: Color::Rgb(0, 130, 0),
+                warn: Color::Rgb(170, 100, 0),
+                bad: Color::Rgb(190, 0, 0),
+                dfu: Color::Magenta,
+                badge_text: Color::White,
+                selection: Color::Rgb(215, 215, 215),
+                arrival: [150, 185, 215, 235].map(|light| Color::Rgb(light, 245, light)),
+                vendors: [
+                    Color::Rgb(0, 110, 150),
+                    Color::Magenta,
+                    Color::Rgb(0, 130, 0),
+                    Color::Blue,
+                    Color::Rgb(150, 60, 0),
+                    Color::Rgb(80, 110, 0),
+                    Color::Rgb(120, 0, 150),
+                    Color::Rgb(0, 120, 110),
+                ],
+            },
+            // Bright colors only, nothing dim enough to vanish on odd palettes
+            ThemeName::HighContrast => Self {
+                text: Color::White,
+                muted: Color::Gray,
+                accent: Color::LightCyan,
+                border: Color::White,
+                good: Color::LightGreen,
+                warn: Color::LightYellow,
+                bad: Color::LightRed,
+                dfu: Color::LightMagenta,
+                badge_text: Color::Black,
+                selection: Color::Blue,
+                arrival: [Color::Green, Color::Green, Color::Blue, Color::Blue],
+                vendors: [
+                    Color::LightCyan,
+                    Color::LightMagenta,
+                    Color::LightGreen,
+                    Color::LightBlue,
+                    Color::White,
+                    Color::LightCyan,
+                    Color::LightMagenta,
+                    Color::LightGreen,
+                ],
+            },
+        }
+    }
+
+    /// The same theme with every color reset to the terminal default, for
+    /// NO_COLOR and --no-color. Bold, dim and the like are modifiers, not
+    /// colors, so the structure they give survives.
+    fn without_color(self) -> Self {
+        Self {
+            text: Color::Reset,
+            muted: Color::Reset,
+            accent: Color::Reset,
+            border: Color::Reset,
+            good: Color::Reset,
+            warn: Color::Reset,
+            bad: Color::Reset,
+            dfu: Color::Reset,
+            badge_text: Color::Reset,
+            selection: Color::Reset,
+            arrival: [Color::Reset; 4],
+            vendors: [Color::Reset; 8],
+        }
+    }
+}
 
-        // Trigger initial refresh
-        let _ = trigger_tx.send(());
+/// NO_COLOR (https://no-color.org) set to anything non-empty, or --no-color
+fn color_disabled(cli: &Cli) -> bool {
+    cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
 
-        let mut app = Self {
-            devices: vec![],
-            list_state: ListState::default(),
-            selected_key: None,
-            should_quit: false,
-            stats: Stats::new(),
-            device_receiver: device_rx,
-            refresh_trigger: trigger_tx,
-        };
+/// How the content area is split, cycled with 'v'
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ViewLayout {
+    /// Device list beside the details panel
+    #[default]
+    Split,
+    /// Full-width list with ID and path on every row
+    Full,
+    /// The list flowed into two columns
+    Columns,
+}
 
-        // Wait for initial data
-        if let Ok((devices, duration)) = app.device_receiver.recv_timeout(Duration::from_secs(1)) {
-            app.update_devices(devices, duration);
+impl ViewLayout {
+    fn next(self) -> Self {
+        match self {
+            ViewLayout::Split => ViewLayout::Full,
+            ViewLayout::Full => ViewLayout::Columns,
+            ViewLayout::Columns => ViewLayout::Split,
         }
-
-        app
     }
 
-    fn update_devices(&mut self, new_devices: Vec<UsbDevice>, refresh_duration: Duration) {
-        // Track connects/disconnects using unique keys
-        let old_keys: HashSet<String> = self.devices.iter().map(|d| d.key()).collect();
-        let new_keys: HashSet<String> = new_devices.iter().map(|d| d.key()).collect();
-
-        if self.stats.refresh_count > 0 {
-            self.stats.connects += new_keys.difference(&old_keys).count() as u64;
-            self.stats.disconnects += old_keys.difference(&new_keys).count() as u64;
+    fn label(self) -> &'static str {
+        match self {
+            ViewLayout::Split => "split",
+            ViewLayout::Full => "full width",
+            ViewLayout::Columns => "two columns",
         }
+    }
+}
 
-        self.devices = new_devices;
-        self.stats.refresh_count += 1;
-        self.stats.last_refresh_duration = refresh_duration;
+/// Device list ordering, cycled with 's'
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortMode {
+    ByBus,
+    ByName,
+    ByVendor,
+    DfuFirst,
+}
 
-        // Update stats
-        if self.devices.len() > self.stats.peak_devices {
-            self.stats.peak_devices = self.devices.len();
-        }
-        for device in &self.devices {
-            self.stats.devices_ever_seen.insert(device.id());
-            if device.is_dfu {
-                self.stats.dfu_devices_ever_seen.insert(device.id());
-            }
+impl SortMode {
+    fn next(self) -> Self {
+        match self {
+            SortMode::ByBus => SortMode::ByName,
+            SortMode::ByName => SortMode::ByVendor,
+            SortMode::ByVendor => SortMode::DfuFirst,
+            SortMode::DfuFirst => SortMode::ByBus,
         }
+    }
 
-        // Restore selection by key
-        if let Some(ref key) = self.selected_key {
-            if let Some(idx) = self.devices.iter().position(|d| d.key() == *key) {
-                self.list_state.select(Some(idx));
-            } else {
-                // Device gone, keep index if valid
-                let current = self.list_state.selected().unwrap_or(0);
-                let new_idx = current.min(self.devices.len().saturating_sub(1));
-                if !self.devices.is_empty() {
-                    self.list_state.select(Some(new_idx));
-                    self.selected_key = Some(self.devices[new_idx].key());
-                }
-            }
-        } else if !self.devices.is_empty() {
-            self.list_state.select(Some(0));
-            self.selected_key = Some(self.devices[0].key());
+    fn label(self) -> &'static str {
+        match self {
+            SortMode::ByBus => "bus",
+            SortMode::ByName => "name",
+            SortMode::ByVendor => "vendor",
+            SortMode::DfuFirst => "dfu first",
         }
     }
 
-    fn try_receive_devices(&mut self) {
-        // Non-blocking receive - only take the latest update
-        let mut latest: Option<(Vec<UsbDevice>, Duration)> = None;
-        while let Ok(update) = self.device_receiver.try_recv() {
-            latest = Some(update);
+    fn sort(self, devices: &mut [UsbDevice]) {
+        // Bus order first so every mode has a stable tiebreak
+        devices.sort_by_key(|d| {
+            (
+                d.bus.parse::<u32>().unwrap_or(0),
+                d.device.parse::<u32>().unwrap_or(0),
+            )
+        });
+        match self {
+            SortMode::ByBus => {}
+            SortMode::ByName => devices.sort_by_key(|d| d.name.to_lowercase()),
+            SortMode::ByVendor => devices.sort_by(|a, b| a.vendor_id.cmp(&b.vendor_id)),
+            SortMode::DfuFirst => devices.sort_by_key(|d| (!d.is_dfu, d.name.to_lowercase())),
         }
-        if let Some((devices, duration)) = latest {
-            self.update_devices(devices, duration);
+    }
+}
+
+/// Which devices the list shows by whether they have a tty, cycled with 'T'
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TtyFilter {
+    All,
+    TtyOnly,
+    NoTty,
+}
+
+impl TtyFilter {
+    fn next(self) -> Self {
+        match self {
+            TtyFilter::All => TtyFilter::TtyOnly,
+            TtyFilter::TtyOnly => TtyFilter::NoTty,
+            TtyFilter::NoTty => TtyFilter::All,
         }
     }
 
-    fn manual_refresh(&mut self) {
-        let _ = self.refresh_trigger.send(());
+    /// Shown in the list title, None when nothing is left out
+    fn label(self) -> Option<&'static str> {
+        match self {
+            TtyFilter::All => None,
+            TtyFilter::TtyOnly => Some("tty only"),
+            TtyFilter::NoTty => Some("no tty"),
+        }
     }
 
-    fn selected_device(&self) -> Option<&UsbDevice> {
-        self.list_state
-            .selected()
-            .and_then(|i| self.devices.get(i))
+    fn matches(self, device: &UsbDevice) -> bool {
+        match self {
+            TtyFilter::All => true,
+            TtyFilter::TtyOnly => !device.tty_paths.is_empty(),
+            TtyFilter::NoTty => device.tty_paths.is_empty(),
+        }
     }
+}
 
-    fn next(&mut self) {
-        if self.devices.is_empty() {
-            return;
+/// Everything a key can be bound to in the device list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Action {
+    Next,
+    Prev,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    Filter,
+    Flash,
+    ToggleLog,
+    Export,
+    ExportCsv,
+    Copy,
+    /// Copy `-d VID:PID` / `-s BUS:DEV`, the way lsusb and friends take them
+    CopyIdArg,
+    CopyBusArg,
+    Sort,
+    Layout,
+    ShrinkList,
+    GrowList,
+    VendorColors,
+    DfuOnly,
+    TtyFilter,
+    HideInternal,
+    FollowNew,
+    Descriptors,
+    OpenSysfs,
+    Note,
+    Pause,
+    Reset,
+    Eject,
+    Authorize,
+    Unbind,
+    ClearStats,
+    Select,
+    KernelLog,
+    Vendors,
+    Baseline,
+    Diff,
+    Tree,
+    Group,
+    /// Toggle, open or close the hub under the cursor in the tree view, or
+    /// the group of identical devices when grouping
+    ToggleExpand,
+    Expand,
+    Collapse,
+    Refresh,
+    Departed,
+    Help,
+    /// Clear the filter, then the selection, then quit
+    Back,
+    Quit,
+}
+
+impl Action {
+    const ALL: [Action; 47] = [
+        Action::Next,
+        Action::Prev,
+        Action::PageDown,
+        Action::PageUp,
+        Action::First,
+        Action::Last,
+        Action::Filter,
+        Action::Flash,
+        Action::ToggleLog,
+        Action::Export,
+        Action::ExportCsv,
+        Action::Copy,
+        Action::CopyIdArg,
+        Action::CopyBusArg,
+        Action::Sort,
+        Action::Layout,
+        Action::ShrinkList,
+        Action::GrowList,
+        Action::VendorColors,
+        Action::DfuOnly,
+        Action::TtyFilter,
+        Action::HideInternal,
+        Action::FollowNew,
+        Action::Descriptors,
+        Action::OpenSysfs,
+        Action::Note,
+        Action::Pause,
+        Action::Reset,
+        Action::Eject,
+        Action::Authorize,
+        Action::Unbind,
+        Action::ClearStats,
+        Action::Select,
+        Action::KernelLog,
+        Action::Vendors,
+        Action::Baseline,
+        Action::Diff,
+        Action::Tree,
+        Action::Group,
+        Action::ToggleExpand,
+        Action::Expand,
+        Action::Collapse,
+        Action::Refresh,
+        Action::Departed,
+        Action::Help,
+        Action::Back,
+        Action::Quit,
+    ];
+
+    /// Name used in the `[keys]` config table
+    fn name(self) -> &'static str {
+        match self {
+            Action::Next => "next",
+            Action::Prev => "prev",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::Filter => "filter",
+            Action::Flash => "flash",
+            Action::ToggleLog => "log",
+            Action::Export => "export",
+            Action::ExportCsv => "export_csv",
+            Action::Copy => "copy",
+            Action::CopyIdArg => "copy_id_arg",
+            Action::CopyBusArg => "copy_bus_arg",
+            Action::Sort => "sort",
+            Action::Layout => "layout",
+            Action::ShrinkList => "shrink_list",
+            Action::GrowList => "grow_list",
+            Action::VendorColors => "colors",
+            Action::DfuOnly => "dfu_only",
+            Action::TtyFilter => "tty_filter",
+            Action::HideInternal => "hide_internal",
+            Action::FollowNew => "follow_new",
+            Action::Descriptors => "descriptors",
+            Action::OpenSysfs => "open_sysfs",
+            Action::Note => "note",
+            Action::Pause => "pause",
+            Action::Reset => "reset",
+            Action::Eject => "eject",
+            Action::Authorize => "authorize",
+            Action::Unbind => "unbind",
+            Action::ClearStats => "clear_stats",
+            Action::Select => "select",
+            Action::KernelLog => "kernel_log",
+            Action::Vendors => "vendors",
+            Action::Baseline => "baseline",
+            Action::Diff => "diff",
+            Action::Tree => "tree",
+            Action::Group => "group",
+            Action::ToggleExpand => "toggle_expand",
+            Action::Expand => "expand",
+            Action::Collapse => "collapse",
+            Action::Refresh => "refresh",
+            Action::Departed => "departed",
+            Action::Help => "help",
+            Action::Back => "back",
+            Action::Quit => "quit",
         }
-        let i = match self.list_state.selected() {
-            Some(i) => {
-                if i >= self.devices.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.list_state.select(Some(i));
-        self.selected_key = Some(self.devices[i].key());
     }
 
-    fn previous(&mut self) {
-        if self.devices.is_empty() {
-            return;
+    /// Footer hint text
+    fn hint(self) -> &'static str {
+        match self {
+            Action::Next => "down",
+            Action::Prev => "up",
+            Action::PageDown => "page down",
+            Action::PageUp => "page up",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::Filter => "search",
+            Action::Flash => "dfu-util",
+            Action::ToggleLog => "log",
+            Action::Export => "export",
+            Action::ExportCsv => "csv",
+            Action::Copy => "copy",
+            Action::CopyIdArg => "copy -d",
+            Action::CopyBusArg => "copy -s",
+            Action::Sort => "sort",
+            Action::Layout => "layout",
+            Action::ShrinkList => "narrower",
+            Action::GrowList => "wider",
+            Action::VendorColors => "colors",
+            Action::DfuOnly => "dfu only",
+            Action::TtyFilter => "tty filter",
+            Action::HideInternal => "hide internal",
+            Action::FollowNew => "follow new",
+            Action::Descriptors => "descriptors",
+            Action::OpenSysfs => "sysfs",
+            Action::Note => "note",
+            Action::Pause => "pause",
+            Action::Reset => "reset",
+            Action::Eject => "eject",
+            Action::Authorize => "authorize",
+            Action::Unbind => "unbind",
+            Action::ClearStats => "clear stats",
+            Action::Select => "select",
+            Action::KernelLog => "kernel log",
+            Action::Vendors => "vendors",
+            Action::Baseline => "baseline",
+            Action::Diff => "diff",
+            Action::Tree => "tree",
+            Action::Group => "group",
+            Action::ToggleExpand => "fold",
+            Action::Expand => "expand",
+            Action::Collapse => "collapse",
+            Action::Refresh => "refresh",
+            Action::Departed => "departed",
+            Action::Help => "help",
+            Action::Back => "back",
+            Action::Quit => "quit",
         }
-        let i = match self.list_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.devices.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.list_state.select(Some(i));
-        self.selected_key = Some(self.devices[i].key());
     }
 
-    fn dfu_count(&self) -> usize {
-        self.devices.iter().filter(|d| d.is_dfu).count()
+    fn default_keys(self) -> Vec<KeyCode> {
+        match self {
+            Action::Next => vec![KeyCode::Down, KeyCode::Char('j')],
+            Action::Prev => vec![KeyCode::Up, KeyCode::Char('k')],
+            Action::PageDown => vec![KeyCode::PageDown],
+            Action::PageUp => vec![KeyCode::PageUp],
+            Action::First => vec![KeyCode::Home],
+            Action::Last => vec![KeyCode::End, KeyCode::Char('G')],
+            Action::Filter => vec![KeyCode::Char('/')],
+            Action::Flash => vec![KeyCode::Char('f')],
+            Action::ToggleLog => vec![KeyCode::Char('l')],
+            Action::Export => vec![KeyCode::Char('e')],
+            Action::ExportCsv => vec![KeyCode::Char('x')],
+            Action::Copy => vec![KeyCode::Char('y')],
+            Action::CopyIdArg => vec![KeyCode::Char('Y')],
+            Action::CopyBusArg => vec![KeyCode::Char('S')],
+            Action::Sort => vec![KeyCode::Char('s')],
+            Action::Layout => vec![KeyCode::Char('v')],
+            Action::ShrinkList => vec![KeyCode::Char('<')],
+            Action::GrowList => vec![KeyCode::Char('>')],
+            Action::VendorColors => vec![KeyCode::Char('c')],
+            Action::DfuOnly => vec![KeyCode::Char('d')],
+            Action::TtyFilter => vec![KeyCode::Char('T')],
+            Action::HideInternal => vec![KeyCode::Char('H')],
+            Action::FollowNew => vec![KeyCode::Char('F')],
+            Action::Descriptors => vec![KeyCode::Char('i')],
+            Action::OpenSysfs => vec![KeyCode::Char('o')],
+            Action::Note => vec![KeyCode::Char('n')],
+            Action::Pause => vec![KeyCode::Char('p')],
+            Action::Reset => vec![KeyCode::Char('R')],
+            Action::Eject => vec![KeyCode::Char('E')],
+            Action::Authorize => vec![KeyCode::Char('A')],
+            Action::Unbind => vec![KeyCode::Char('U')],
+            Action::ClearStats => vec![KeyCode::Char('C')],
+            Action::Select => vec![KeyCode::Char(' ')],
+            Action::KernelLog => vec![KeyCode::Char('K')],
+            Action::Vendors => vec![KeyCode::Char('V')],
+            Action::Baseline => vec![KeyCode::Char('b')],
+            Action::Diff => vec![KeyCode::Char('B')],
+            Action::Tree => vec![KeyCode::Char('t')],
+            Action::Group => vec![KeyCode::Char('D')],
+            Action::ToggleExpand => vec![KeyCode::Enter],
+            Action::Expand => vec![KeyCode::Right],
+            Action::Collapse => vec![KeyCode::Left],
+            Action::Refresh => vec![KeyCode::Char('r')],
+            Action::Departed => vec![KeyCode::Char('u')],
+            Action::Help => vec![KeyCode::Char('?')],
+            Action::Back => vec![KeyCode::Esc],
+            Action::Quit => vec![KeyCode::Char('q')],
+        }
     }
 }
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
-    let terminal = ratatui::init();
-    let result = run(terminal);
-    ratatui::restore();
-    result
+/// Parse a config key name: a single character, or one of the named keys
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let key = match name.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "delete" => KeyCode::Delete,
+        other => KeyCode::F(other.strip_prefix('f')?.parse().ok()?),
+    };
+    Some(key)
 }
 
-fn run(mut terminal: DefaultTerminal) -> Result<()> {
-    let mut app = App::new();
+/// Short label for a key in hints
+fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "space".into(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "↑".into(),
+        KeyCode::Down => "↓".into(),
+        KeyCode::Left => "←".into(),
+        KeyCode::Right => "→".into(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other).to_lowercase(),
+    }
+}
 
-    loop {
-        // Check for new device data (non-blocking)
-        app.try_receive_devices();
+/// Which action each key triggers in the device list
+struct Keymap {
+    bindings: HashMap<KeyCode, Action>,
+    keys: HashMap<Action, Vec<KeyCode>>, // Action -> its keys, first one shown in hints
+}
 
-        terminal.draw(|frame| ui(frame, &mut app))?;
+impl Keymap {
+    /// Defaults overridden by the config's `[keys]` table. Problems come
+    /// back as warnings; the offending entry is skipped.
+    fn from_config(config: &HashMap<String, KeyNames>) -> (Self, Vec<String>) {
+        let mut warnings = vec![];
+        let mut keys: HashMap<Action, Vec<KeyCode>> = Action::ALL
+            .iter()
+            .map(|&action| (action, action.default_keys()))
+            .collect();
+
+        let mut entries: Vec<(&String, &KeyNames)> = config.iter().collect();
+        entries.sort_by_key(|(name, _)| name.as_str());
+        for (name, names) in entries {
+            let Some(action) = Action::ALL.into_iter().find(|a| a.name() == name) else {
+                warnings.push(format!("unknown action \"{}\"", name));
+                continue;
+            };
+            let names = match names {
+                KeyNames::One(name) => std::slice::from_ref(name),
+                KeyNames::Many(names) => names.as_slice(),
+            };
+            let mut codes = vec![];
+            for key in names {
+                match parse_key(key) {
+                    Some(code) => codes.push(code),
+                    None => warnings.push(format!("unknown key \"{}\" for {}", key, name)),
+                }
+            }
+            // All of them unparseable: keep the defaults rather than leave it unbound
+            if codes.is_empty() && !names.is_empty() {
+                continue;
+            }
+            keys.insert(action, codes);
+        }
+
+        // Rebinding a key to a new action takes it away from its default one
+        let overridden: Vec<KeyCode> = config
+            .keys()
+            .filter_map(|name| Action::ALL.into_iter().find(|a| a.name() == name))
+            .flat_map(|action| keys[&action].clone())
+            .collect();
+        for (action, codes) in keys.iter_mut() {
+            if !config.contains_key(action.name()) {
+                codes.retain(|code| !overridden.contains(code));
+            }
+        }
+
+        let mut bindings = HashMap::new();
+        for action in Action::ALL {
+            for &code in &keys[&action] {
+                if let Some(previous) = bindings.insert(code, action) {
+                    warnings.push(format!(
+                        "{} is bound to both {} and {}",
+                        key_label(code),
+                        previous.name(),
+                        action.name()
+                    ));
+                }
+            }
+        }
+        (Self { bindings, keys }, warnings)
+    }
+
+    fn action(&self, key: KeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    /// Label of the first key bound to an action, if any
+    fn label(&self, action: Action) -> Option<String> {
+        self.keys.get(&action)?.first().map(|&key| key_label(key))
+    }
+
+    /// Every key bound to an action, for the help overlay
+    fn labels(&self, action: Action) -> Vec<String> {
+        self.keys
+            .get(&action)
+            .into_iter()
+            .flatten()
+            .map(|&key| key_label(key))
+            .collect()
+    }
+}
+
+/// What the kernel log thread sends back
+enum KernelMessage {
+    Line(String),
+    /// Neither journalctl nor /dev/kmsg could be read; the reason is shown in the panel
+    Unavailable(String),
+}
+
+/// USB lines from the kernel log, tailed on a background thread
+struct KernelLog {
+    receiver: Receiver<KernelMessage>,
+    lines: VecDeque<String>,
+    unavailable: Option<String>,
+    /// The `journalctl -f` being tailed, once the thread has started it
+    journal: Arc<Mutex<Option<Child>>>,
+}
+
+impl KernelLog {
+    fn spawn() -> Self {
+        let (tx, rx) = mpsc::channel();
+        let journal = Arc::new(Mutex::new(None));
+        let slot = Arc::clone(&journal);
+        thread::spawn(move || tail_kernel_log(tx, &slot));
+        Self {
+            receiver: rx,
+            lines: VecDeque::new(),
+            unavailable: None,
+            journal,
+        }
+    }
+}
+
+impl Drop for KernelLog {
+    /// `journalctl -f` only notices a closed pipe when it next writes, which
+    /// on a quiet bus could be never
+    fn drop(&mut self) {
+        let child = self.journal.lock().ok().and_then(|mut slot| slot.take());
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn is_usb_line(line: &str) -> bool {
+    line.to_lowercase().contains("usb")
+}
+
+/// Whether journalctl can show kernel messages at all. Following with `-f`
+/// just sits silent when it can't, so it's asked for one line first.
+fn journal_readable() -> bool {
+    Command::new("journalctl")
+        .args(["-k", "-n", "1", "-q"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+/// Follow `journalctl -k`, falling back to /dev/kmsg when journalctl is
+/// missing or refuses. The journalctl child goes in `journal` so the
+/// `KernelLog` can kill it. Returns once the receiving side is gone.
+fn tail_kernel_log(tx: Sender<KernelMessage>, journal: &Mutex<Option<Child>>) {
+    use std::io::{BufRead, BufReader};
+
+    let follow = || {
+        Command::new("journalctl")
+            .args(["-k", "-f", "-n", "200", "-o", "short", "-q"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+    };
+    if journal_readable() {
+        if let Ok(mut child) = follow() {
+            let stdout = child.stdout.take();
+            if let Ok(mut slot) = journal.lock() {
+                *slot = Some(child);
+            }
+            if let Some(stdout) = stdout {
+                for line in BufReader::new(stdout)
+                    .lines()
+                    .map_while(std::result::Result::ok)
+                {
+                    if is_usb_line(&line) && tx.send(KernelMessage::Line(line)).is_err() {
+                        break;
+                    }
+                }
+            }
+            // Reap it here unless the KernelLog already did on drop
+            let child = journal.lock().ok().and_then(|mut slot| slot.take());
+            if let Some(mut child) = child {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            return;
+        }
+    }
+
+    // /dev/kmsg records look like "6,1234,5678,-;usb 1-3: new device"
+    match fs::File::open("/dev/kmsg") {
+        Ok(file) => {
+            for record in BufReader::new(file).lines() {
+                let record = match record {
+                    Ok(record) => record,
+                    // The ring buffer overwrote records we hadn't read yet;
+                    // the next read picks up at the oldest one left
+                    Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => continue,
+                    Err(err) => {
+                        let reason = format!("stopped reading the kernel log: {}", err);
+                        tracing::warn!(%reason, "kernel_log_unavailable");
+                        let _ = tx.send(KernelMessage::Unavailable(reason));
+                        return;
+                    }
+                };
+                let message = record.split_once(';').map_or(record.as_str(), |(_, m)| m);
+                if is_usb_line(message)
+                    && tx.send(KernelMessage::Line(message.to_string())).is_err()
+                {
+                    return;
+                }
+            }
+        }
+        Err(err) => {
+            let reason = if err.kind() == std::io::ErrorKind::PermissionDenied {
+                "no permission to read the kernel log (try the adm or systemd-journal group)"
+                    .to_string()
+            } else {
+                format!("can't read the kernel log: {}", err)
+            };
+            tracing::warn!(%reason, "kernel_log_unavailable");
+            let _ = tx.send(KernelMessage::Unavailable(reason));
+        }
+    }
+}
+
+/// Whether a kernel log line is about this device: by its sysfs name
+/// ("usb 1-3.2: ..."), or by bus and device number
+fn mentions_device(line: &str, device: &UsbDevice) -> bool {
+    if !device.port_path.is_empty() {
+        let name = device.port_path_string();
+        let mentioned = line.match_indices(&name).any(|(at, _)| {
+            let before = line[..at].chars().next_back();
+            let after = line[at + name.len()..].chars().next();
+            !before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+                && matches!(after, Some(':') | Some(' ') | None)
+        });
+        if mentioned {
+            return true;
+        }
+    }
+    let bus: u32 = device.bus.parse().unwrap_or(0);
+    let dev: u32 = device.device.parse().unwrap_or(0);
+    let lower = line.to_lowercase();
+    [
+        format!("bus {:03} device {:03}", bus, dev),
+        format!("bus {} device {}", bus, dev),
+    ]
+    .iter()
+    .any(|pattern| lower.contains(pattern))
+}
+
+/// Full-screen descriptor overlay for one device
+struct DescriptorView {
+    key: String,
+    scroll: u16,
+}
+
+/// The 'n' input box: the note being typed for one device identity
+struct NoteEditor {
+    identity: String,
+    name: String,
+    /// No serial, so the note lands on every device with this VID:PID
+    shared: bool,
+    text: String,
+}
+
+/// Free-text labels by device identity, in notes.toml next to the config
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Notes(BTreeMap<String, String>);
+
+impl Notes {
+    fn path() -> Option<PathBuf> {
+        Config::path().map(|config| config.with_file_name("notes.toml"))
+    }
+
+    /// Load notes.toml. A missing file starts empty; a broken one starts
+    /// empty plus a warning, like `Config::load`.
+    fn load() -> (Self, Option<String>) {
+        let Some(path) = Self::path() else {
+            return (Self::default(), None);
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return (Self::default(), None)
+            }
+            Err(err) => {
+                return (
+                    Self::default(),
+                    Some(format!("couldn't read {}: {}", path.display(), err)),
+                )
+            }
+        };
+        match toml::from_str(&contents) {
+            Ok(notes) => (notes, None),
+            Err(err) => (
+                Self::default(),
+                Some(format!(
+                    "notes error in {}: {}",
+                    path.display(),
+                    err.message()
+                )),
+            ),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn get(&self, device: &UsbDevice) -> Option<&str> {
+        self.0.get(&device.identity()).map(String::as_str)
+    }
+}
+
+/// Devices captured with 'b', to diff the live list against
+struct Baseline {
+    taken_at: DateTime<Local>,
+    devices: Vec<UsbDevice>,
+}
+
+/// What differs between the baseline and now
+struct SnapshotDiff<'a> {
+    added: Vec<&'a UsbDevice>,
+    removed: Vec<&'a UsbDevice>,
+    changed: Vec<(&'a UsbDevice, Vec<String>)>, // Current device and what changed about it
+}
+
+impl<'a> SnapshotDiff<'a> {
+    fn new(baseline: &'a [UsbDevice], current: &'a [UsbDevice]) -> Self {
+        let before: HashMap<String, &UsbDevice> = baseline.iter().map(|d| (d.key(), d)).collect();
+        let after: HashSet<String> = current.iter().map(|d| d.key()).collect();
+
+        let mut diff = Self {
+            added: vec![],
+            removed: baseline
+                .iter()
+                .filter(|d| !after.contains(&d.key()))
+                .collect(),
+            changed: vec![],
+        };
+        for device in current {
+            let Some(old) = before.get(&device.key()) else {
+                diff.added.push(device);
+                continue;
+            };
+            let mut changes = vec![];
+            if old.tty_paths != device.tty_paths {
+                changes.push(format!("tty {} → {}", tty_list(old), tty_list(device)));
+            }
+            if old.drivers != device.drivers {
+                let drivers = |d: &UsbDevice| {
+                    if d.drivers.is_empty() {
+                        "none".to_string()
+                    } else {
+                        d.drivers.join(",")
+                    }
+                };
+                changes.push(format!("driver {} → {}", drivers(old), drivers(device)));
+            }
+            if old.speed != device.speed {
+                let speed = |d: &UsbDevice| d.speed.map_or("unknown", |speed| speed.label());
+                changes.push(format!("speed {} → {}", speed(old), speed(device)));
+            }
+            if !changes.is_empty() {
+                diff.changed.push((device, changes));
+            }
+        }
+        diff
+    }
+}
+
+/// A device counts as active for this long after its URB count last rose
+const ACTIVITY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Spinner shown next to active devices, one frame per 100ms
+const ACTIVITY_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long a newly plugged-in device's row stays highlighted
+const ARRIVAL_HIGHLIGHT: Duration = Duration::from_secs(3);
+
+/// Minimum gap between desktop notifications for the same DFU device
+const NOTIFY_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// A destructive action waiting for 'y' in the confirmation overlay
+enum PendingAction {
+    /// dfu-util on these devices; `skipped` targets weren't in DFU mode
+    Flash {
+        devices: Vec<UsbDevice>,
+        skipped: usize,
+    },
+    Reset(Vec<UsbDevice>),
+    /// Detach these drivers from the cursor device's interfaces
+    Unbind {
+        device: Box<UsbDevice>,
+        interfaces: Vec<BoundInterface>,
+    },
+    /// Cut the device off; authorizing it again needs no prompt
+    Deauthorize(Box<UsbDevice>),
+}
+
+impl PendingAction {
+    fn devices(&self) -> &[UsbDevice] {
+        match self {
+            PendingAction::Flash { devices, .. } | PendingAction::Reset(devices) => devices,
+            PendingAction::Unbind { device, .. } | PendingAction::Deauthorize(device) => {
+                std::slice::from_ref(device)
+            }
+        }
+    }
+}
+
+/// How long a footer status message stays visible
+const STATUS_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// Transient message shown in the footer
+struct StatusMessage {
+    text: String,
+    color: Color,
+    shown_at: Instant,
+}
+
+struct App {
+    devices: Vec<UsbDevice>,
+    list_state: ListState,
+    selected_key: Option<String>, // Track selection by device key, not index
+    selected_keys: HashSet<String>, // Multi-selection for batch actions
+    filter_query: String,
+    filter_regex: Option<Result<Regex, regex::Error>>, // Compiled query when it starts with '~'
+    searching: bool,                                   // Typing into the filter box
+    sort_mode: SortMode,
+    dfu_only: bool,
+    tty_filter: TtyFilter,
+    tty_filter_anchor: Option<String>, // Selected before a tty mode hid it; reselected once shown again
+    hide_internal: bool, // Root hubs and config `ignore` matches are left out of the list
+    follow_new: bool,    // Jump to each newly connected device until the cursor is moved by hand
+    highlight_tty: Option<String>, // --highlight: the tty whose device stays marked and selected
+    highlight_pinned: bool, // The selection follows that device; moving off it unpins
+    bus_filter: Option<u32>,
+    scope: IdScope,
+    rescoped: bool, // Bus filter changed, so the next poll's differences aren't real events
+    tree_view: bool,
+    split_pct: u16,             // Device list share of the split layout
+    collapsed: HashSet<String>, // Port paths of hubs folded in the tree view
+    group_duplicates: bool,
+    expanded_groups: HashSet<String>, // VID:PIDs whose identical devices are listed one by one
+    vendor_colors: bool,              // Tint each row's vendor by vendor ID, toggled with 'c'
+    paused: bool,
+    apply_next_update: bool, // Manual refresh while paused lets one update through
+    should_quit: bool,
+    quit_during_eject: bool, // Quit pressed once while an eject runs; the next one exits anyway
+    pending: Option<PendingAction>,
+    status: Option<StatusMessage>,
+    log: VecDeque<LogEntry>,
+    show_log: bool,
+    kernel_log: Option<KernelLog>, // Running while the panel is shown
+    show_kernel_log: bool,
+    show_vendors: bool,
+    first_seen: HashMap<String, Instant>, // Device key -> when it (re)appeared
+    departed: HashMap<String, (UsbDevice, Instant)>, // Device key -> the device and when it left
+    show_departed: bool,                  // Departed devices listed below the live ones
+    arrived_at: HashMap<String, Instant>, // Keys plugged in after startup, for the row highlight
+    transitions: HashMap<String, VecDeque<Instant>>, // Identity -> recent appear/vanish times
+    last_active: HashMap<String, Instant>, // Key -> when its URB count last went up
+    theme: Theme,
+    /// How to run lsusb, for polling and for `lsusb -v` descriptor dumps
+    lsusb: LsusbOptions,
+    config: Config,
+    keymap: Keymap,
+    descriptor_cache: HashMap<String, Vec<String>>, // Device key -> descriptor dump
+    descriptor_view: Option<DescriptorView>,
+    descriptor_fetch: Option<(String, Receiver<Vec<String>>)>, // `lsusb -v` still running for this key
+    dfu_descriptors: HashMap<String, Option<DfuDescriptor>>, // Device key -> DFU functional descriptor, if it has one
+    dfu_fetch: Option<(String, Receiver<Option<DfuDescriptor>>)>, // Still reading it for this key
+    eject_job: Option<(String, Receiver<std::io::Result<usize>>)>, // udisksctl running for this name
+    hub_switching: HashMap<String, Option<PowerSwitching>>, // Hub key -> power switching mode, if readable
+    hub_switching_fetch: Option<(String, Receiver<Option<PowerSwitching>>)>, // Still asking this hub
+    frame_count: u64, // Frames drawn, to step the spinner
+    notes: Notes,
+    notes_warning: Option<String>, // Why notes.toml didn't load; saving would wipe it
+    note_editor: Option<NoteEditor>,
+    baseline: Option<Baseline>,
+    show_diff: bool,
+    show_help: bool,                // Full-screen key reference, opened with '?'
+    list_panes: Vec<(Rect, usize)>, // Each list column last drawn and its first row, for mouse hit-testing
+    // Kept alive so X11 clipboard contents survive until we quit
+    clipboard: Option<arboard::Clipboard>,
+    stats: Stats,
+    persist_stats: bool,
+    notify: bool,
+    notified_at: HashMap<String, Instant>, // Device identity -> last DFU notification
+    on_connect: Option<String>,            // --on-connect command template
+    on_disconnect: Option<String>,         // --on-disconnect command template
+    device_receiver: Receiver<PollResult>,
+    poller: Poller,
+    backend: Backend,
+    poll_interval: Duration,
+}
+
+impl App {
+    fn new(cli: &Cli, config: Config) -> Self {
+        let backend = backend(cli);
+        let poll_interval = Duration::from_millis(cli.interval_ms);
+        let lsusb = lsusb_options(cli, &config);
+        let (keymap, key_warnings) = Keymap::from_config(&config.keys);
+        let (notes, notes_warning) = Notes::load();
+
+        // Background thread for USB polling, with an immediate first scan
+        let (poller, device_rx) = Poller::spawn(
+            backend,
+            load_usb_ids(),
+            config.dfu.clone(),
+            lsusb.clone(),
+            poll_interval,
+        );
+        poller.refresh();
+
+        let mut app = Self {
+            devices: vec![],
+            list_state: ListState::default(),
+            selected_key: None,
+            selected_keys: HashSet::new(),
+            filter_query: String::new(),
+            filter_regex: None,
+            searching: false,
+            sort_mode: SortMode::ByBus,
+            dfu_only: false,
+            tty_filter: TtyFilter::All,
+            tty_filter_anchor: None,
+            hide_internal: config.hide_internal,
+            follow_new: false,
+            highlight_tty: cli.highlight.clone(),
+            highlight_pinned: cli.highlight.is_some(),
+            bus_filter: cli.bus,
+            scope: IdScope::new(cli),
+            rescoped: false,
+            tree_view: false,
+            split_pct: config.split_pct.clamp(SPLIT_MIN, SPLIT_MAX),
+            collapsed: HashSet::new(),
+            group_duplicates: false,
+            expanded_groups: HashSet::new(),
+            vendor_colors: true,
+            paused: false,
+            apply_next_update: false,
+            should_quit: false,
+            quit_during_eject: false,
+            pending: None,
+            status: None,
+            log: VecDeque::new(),
+            show_log: false,
+            kernel_log: None,
+            show_kernel_log: false,
+            show_vendors: false,
+            first_seen: HashMap::new(),
+            departed: HashMap::new(),
+            show_departed: false,
+            arrived_at: HashMap::new(),
+            transitions: HashMap::new(),
+            last_active: HashMap::new(),
+            theme: match Theme::new(cli.theme.unwrap_or(config.theme)) {
+                theme if color_disabled(cli) => theme.without_color(),
+                theme => theme,
+            },
+            lsusb,
+            config,
+            keymap,
+            descriptor_cache: HashMap::new(),
+            descriptor_view: None,
+            descriptor_fetch: None,
+            dfu_descriptors: HashMap::new(),
+            dfu_fetch: None,
+            eject_job: None,
+            hub_switching: HashMap::new(),
+            hub_switching_fetch: None,
+            frame_count: 0,
+            notes,
+            notes_warning,
+            note_editor: None,
+            baseline: None,
+            show_diff: false,
+            show_help: false,
+            list_panes: vec![],
+            clipboard: None,
+            stats: Stats::new(!cli.no_persist, poll_interval),
+            persist_stats: !cli.no_persist,
+            notify: cli.notify,
+            on_connect: cli.on_connect.clone(),
+            on_disconnect: cli.on_disconnect.clone(),
+            notified_at: HashMap::new(),
+            device_receiver: device_rx,
+            poller,
+            backend,
+            poll_interval,
+        };
+
+        // Wait for initial data
+        if let Ok((devices, duration, started)) =
+            app.device_receiver.recv_timeout(Duration::from_secs(1))
+        {
+            app.update_devices(devices, duration, started);
+        }
+
+        if !key_warnings.is_empty() {
+            app.set_status(
+                format!("[keys] {}", key_warnings.join("; ")),
+                app.theme.warn,
+            );
+        }
+        if let Some(warning) = app.notes_warning.clone() {
+            app.set_status(warning, app.theme.warn);
+        }
+        app
+    }
+
+    fn dispatch(&mut self, action: Action) {
+        match action {
+            Action::Next => self.next(),
+            Action::Prev => self.previous(),
+            Action::PageDown => self.page(1),
+            Action::PageUp => self.page(-1),
+            Action::First => self.select_visible(0),
+            Action::Last => {
+                let last = self.visible_devices().len().saturating_sub(1);
+                self.select_visible(last);
+            }
+            Action::Filter => self.start_search(),
+            Action::Flash => self.request_flash(),
+            Action::ToggleLog => self.toggle_log(),
+            Action::Export => self.export_devices(),
+            Action::ExportCsv => self.export_devices_csv(),
+            Action::Copy => self.copy_selected_path(),
+            Action::CopyIdArg => self.copy_selected_arg(|d| format!("-d {}", d.id())),
+            Action::CopyBusArg => self.copy_selected_arg(|d| format!("-s {}:{}", d.bus, d.device)),
+            Action::Sort => self.cycle_sort(),
+            Action::Layout => self.cycle_layout(),
+            Action::ShrinkList => self.adjust_split(-SPLIT_STEP),
+            Action::GrowList => self.adjust_split(SPLIT_STEP),
+            Action::VendorColors => self.toggle_vendor_colors(),
+            Action::DfuOnly => self.toggle_dfu_only(),
+            Action::TtyFilter => self.cycle_tty_filter(),
+            Action::HideInternal => self.toggle_hide_internal(),
+            Action::FollowNew => self.toggle_follow_new(),
+            Action::Descriptors => self.open_descriptors(),
+            // A replayed listing's bus and device numbers name devices on
+            // another machine; acting on them here would hit whatever has them
+            Action::OpenSysfs
+            | Action::Reset
+            | Action::Eject
+            | Action::Authorize
+            | Action::Unbind
+                if self.lsusb.file.is_some() =>
+            {
+                self.set_status(
+                    format!("No {} while replaying --lsusb-file", action.hint()),
+                    self.theme.warn,
+                );
+            }
+            Action::OpenSysfs => self.open_sysfs_dir(),
+            Action::Note => self.open_note_editor(),
+            Action::Pause => self.toggle_pause(),
+            Action::Reset => self.request_reset(),
+            Action::Eject => self.eject(),
+            Action::Authorize => self.toggle_authorized(),
+            Action::Unbind => self.request_unbind(),
+            Action::ClearStats => self.clear_stats(),
+            Action::Select => self.toggle_marked(),
+            Action::KernelLog => self.toggle_kernel_log(),
+            Action::Vendors => self.show_vendors = !self.show_vendors,
+            Action::Baseline => self.take_baseline(),
+            Action::Diff => self.toggle_diff(),
+            Action::Tree => self.toggle_tree(),
+            Action::Group => self.toggle_grouping(),
+            Action::ToggleExpand => self.set_expanded(None),
+            Action::Expand => self.set_expanded(Some(true)),
+            Action::Collapse => self.set_expanded(Some(false)),
+            Action::Refresh => self.manual_refresh(),
+            Action::Departed => self.show_departed = !self.show_departed,
+            Action::Help => self.show_help = true,
+            Action::Back if !self.filter_query.is_empty() => self.clear_search(),
+            Action::Back if !self.selected_keys.is_empty() => self.clear_marked(),
+            Action::Back | Action::Quit if self.eject_job.is_some() && !self.quit_during_eject => {
+                self.quit_during_eject = true;
+                let hint = match self.keymap.label(Action::Quit) {
+                    Some(key) => {
+                        format!("Operation in progress — press {} again to force quit", key)
+                    }
+                    None => "Operation in progress — quit again to force quit".to_string(),
+                };
+                self.set_status(hint, self.theme.warn);
+            }
+            Action::Back | Action::Quit => self.should_quit = true,
+        }
+    }
+
+    fn update_devices(&mut self, poll: Enumeration, refresh_duration: Duration, started: Instant) {
+        let mut new_devices = poll.devices;
+        new_devices.retain(|d| on_bus(d, self.bus_filter) && self.scope.contains(d));
+        self.stats.parse_failures = poll.parse_failures;
+        self.stats.warning_count += poll.warnings.len() as u64;
+        self.stats.last_warning = poll.warnings.last().cloned();
+        let track_events = self.stats.refresh_count > 0 && !std::mem::take(&mut self.rescoped);
+        let now = Instant::now();
+        // Both compare against the previous poll, so they run before the merge
+        self.record_transitions(&new_devices, now);
+        self.record_activity(&new_devices, now);
+
+        let new_keys: HashSet<String> = new_devices.iter().map(|d| d.key()).collect();
+        let (arrived, gone) = merge_poll(&mut self.devices, new_devices);
+
+        if track_events {
+            self.stats.connects += arrived.len() as u64;
+            self.stats.disconnects += gone.len() as u64;
+            self.stats.record_events(&arrived, &gone, now);
+            for device in &gone {
+                trace_transition(false, device);
+            }
+            for device in &arrived {
+                trace_transition(true, device);
+            }
+            if let Some(template) = self.on_disconnect.clone() {
+                for device in &gone {
+                    self.run_hook(&template, device);
+                }
+            }
+            if let Some(template) = self.on_connect.clone() {
+                for device in &arrived {
+                    self.run_hook(&template, device);
+                }
+            }
+
+            let timestamp = Local::now();
+            let entry = |device: &UsbDevice, kind| LogEntry {
+                timestamp,
+                at: now,
+                key: device.key(),
+                name: device.name.clone(),
+                kind,
+            };
+            let entries: Vec<LogEntry> = gone
+                .iter()
+                .map(|d| entry(d, LogKind::Disconnect))
+                .chain(arrived.iter().map(|d| entry(d, LogKind::Connect)))
+                .collect();
+            for entry in entries {
+                self.push_log(entry);
+            }
+
+            if self.notify {
+                for device in arrived.iter().filter(|d| d.is_dfu) {
+                    self.notify_dfu(device);
+                }
+            }
+
+            if self.follow_new {
+                // A bootloader is usually what's being waited for
+                let listed: Vec<&UsbDevice> =
+                    arrived.iter().filter(|d| self.is_listed(d)).collect();
+                let target = listed
+                    .iter()
+                    .find(|d| d.is_dfu)
+                    .or(listed.first())
+                    .map(|d| d.key());
+                if target.is_some() {
+                    self.selected_key = target;
+                }
+            }
+        }
+
+        // Connection age: drop departed keys so a reconnect starts a fresh timer
+        self.first_seen.retain(|key, _| new_keys.contains(key));
+        self.descriptor_cache
+            .retain(|key, _| new_keys.contains(key));
+        self.dfu_descriptors.retain(|key, _| new_keys.contains(key));
+        self.hub_switching.retain(|key, _| new_keys.contains(key));
+        self.selected_keys.retain(|key| new_keys.contains(key));
+        for key in &new_keys {
+            self.first_seen.entry(key.clone()).or_insert(now);
+        }
+        self.arrived_at
+            .retain(|_, at| now.duration_since(*at) < ARRIVAL_HIGHLIGHT);
+        if track_events {
+            for device in &arrived {
+                self.arrived_at.insert(device.key(), now);
+            }
+            for device in gone {
+                self.departed.insert(device.key(), (device, now));
+            }
+        }
+        // A reconnect gets a new device number, so match the departed by identity
+        let retention = Duration::from_secs(self.config.departed_retention_secs);
+        let present: HashSet<String> = self.devices.iter().map(|d| d.identity()).collect();
+        self.departed.retain(|_, (device, left)| {
+            !present.contains(&device.identity()) && now.duration_since(*left) < retention
+        });
+
+        self.track_highlight();
+        self.sort_mode.sort(&mut self.devices);
+        self.stats.refresh_count += 1;
+        self.stats.last_refresh_duration = refresh_duration;
+
+        // Update stats
+        self.stats.record_count(self.devices.len(), now);
+        if self.devices.len() > self.stats.peak_devices {
+            self.stats.peak_devices = self.devices.len();
+        }
+        for device in &self.devices {
+            self.stats.devices_ever_seen.insert(device.id());
+            if device.is_dfu {
+                self.stats.dfu_devices_ever_seen.insert(device.id());
+            }
+        }
+
+        self.sync_selection();
+        self.stats.end_to_end_latency = started.elapsed();
+    }
+
+    /// Open the descriptor overlay for the selected device, fetching it in
+    /// the background on first use; `lsusb -v` can take a second or two
+    fn open_descriptors(&mut self) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        let key = device.key();
+        let pending = self
+            .descriptor_fetch
+            .as_ref()
+            .is_some_and(|(fetching, _)| *fetching == key);
+        if !self.descriptor_cache.contains_key(&key) && !pending {
+            let (sender, receiver) = mpsc::channel();
+            let lsusb = self.lsusb.clone();
+            thread::spawn(move || {
+                let _ = sender.send(descriptor_lines(&device, &lsusb));
+            });
+            self.descriptor_fetch = Some((key.clone(), receiver));
+        }
+        self.descriptor_view = Some(DescriptorView { key, scroll: 0 });
+    }
+
+    fn try_receive_descriptors(&mut self) {
+        let Some((key, receiver)) = &self.descriptor_fetch else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(lines) => {
+                self.descriptor_cache.insert(key.clone(), lines);
+                self.descriptor_fetch = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.descriptor_fetch = None,
+        }
+    }
+
+    /// Start reading the selected DFU device's functional descriptor for the
+    /// DFU panel, unless it's known or already on its way
+    fn fetch_dfu_descriptor(&mut self) {
+        if self.dfu_fetch.is_some() {
+            return;
+        }
+        let Some(device) = self.selected_device().filter(|d| d.is_dfu).cloned() else {
+            return;
+        };
+        let key = device.key();
+        if self.dfu_descriptors.contains_key(&key) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let lsusb = self.lsusb.clone();
+        thread::spawn(move || {
+            let _ = sender.send(dfu_descriptor(&device, &lsusb));
+        });
+        self.dfu_fetch = Some((key, receiver));
+    }
+
+    /// Start asking the selected hub for its power switching mode for the
+    /// port list, once per hub. A hub that doesn't answer holds the control
+    /// transfer until its timeout, so it runs off the UI thread.
+    fn fetch_hub_switching(&mut self) {
+        if self.lsusb.file.is_some() || self.hub_switching_fetch.is_some() {
+            return;
+        }
+        let Some(device) = self
+            .selected_device()
+            .filter(|d| d.class_code == Some(0x09))
+            .cloned()
+        else {
+            return;
+        };
+        let key = device.key();
+        if self.hub_switching.contains_key(&key) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(hub_power_switching(&device));
+        });
+        self.hub_switching_fetch = Some((key, receiver));
+    }
+
+    fn try_receive_hub_switching(&mut self) {
+        let Some((key, receiver)) = &self.hub_switching_fetch else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(switching) => {
+                self.hub_switching.insert(key.clone(), switching);
+                self.hub_switching_fetch = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.hub_switching_fetch = None,
+        }
+    }
+
+    fn try_receive_dfu_descriptor(&mut self) {
+        let Some((key, receiver)) = &self.dfu_fetch else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(descriptor) => {
+                self.dfu_descriptors.insert(key.clone(), descriptor);
+                self.dfu_fetch = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.dfu_fetch = None,
+        }
+    }
+
+    /// The in-TUI work the footer spinner is standing in for, if any
+    fn busy_label(&self) -> Option<&'static str> {
+        if self.descriptor_fetch.is_some() {
+            Some("fetching descriptors…")
+        } else if self.eject_job.is_some() {
+            Some("ejecting…")
+        } else {
+            self.dfu_fetch.as_ref().map(|_| "reading DFU descriptor…")
+        }
+    }
+
+    fn take_baseline(&mut self) {
+        self.baseline = Some(Baseline {
+            taken_at: Local::now(),
+            devices: self.devices.clone(),
+        });
+        self.set_status(
+            format!("Baseline: {} devices", self.devices.len()),
+            self.theme.accent,
+        );
+    }
+
+    fn toggle_diff(&mut self) {
+        if self.baseline.is_none() {
+            let hint = match self.keymap.label(Action::Baseline) {
+                Some(key) => format!("No baseline yet, press {} first", key),
+                None => "No baseline yet".to_string(),
+            };
+            self.set_status(hint, self.theme.warn);
+            return;
+        }
+        self.show_diff = !self.show_diff;
+    }
+
+    fn close_descriptors(&mut self) {
+        self.descriptor_view = None;
+    }
+
+    fn scroll_descriptors(&mut self, delta: i32) {
+        let Some(view) = self.descriptor_view.as_mut() else {
+            return;
+        };
+        let max = self
+            .descriptor_cache
+            .get(&view.key)
+            .map_or(0, |lines| lines.len().saturating_sub(1)) as i32;
+        view.scroll = (view.scroll as i32 + delta).clamp(0, max) as u16;
+    }
+
+    fn cycle_layout(&mut self) {
+        let layout = self.config.layout.next();
+        self.config.layout = layout;
+        match Config::save_key("layout", layout) {
+            Ok(()) => self.set_status(format!("Layout: {}", layout.label()), self.theme.accent),
+            Err(err) => self.set_status(
+                format!("Layout: {} (not saved: {})", layout.label(), err),
+                self.theme.warn,
+            ),
+        }
+    }
+
+    /// Widen (positive) or narrow the device list in the split layout
+    fn adjust_split(&mut self, delta: i16) {
+        let split_pct = self
+            .split_pct
+            .saturating_add_signed(delta)
+            .clamp(SPLIT_MIN, SPLIT_MAX);
+        if split_pct == self.split_pct {
+            return;
+        }
+        self.split_pct = split_pct;
+        self.config.split_pct = split_pct;
+        match Config::save_key("split_pct", split_pct) {
+            Ok(()) => self.set_status(format!("List width: {}%", split_pct), self.theme.accent),
+            Err(err) => self.set_status(
+                format!("List width: {}% (not saved: {})", split_pct, err),
+                self.theme.warn,
+            ),
+        }
+    }
+
+    fn cycle_sort(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.sort_mode.sort(&mut self.devices);
+        self.sync_selection();
+    }
+
+    /// Note which devices had URBs submitted since the last poll
+    fn record_activity(&mut self, new_devices: &[UsbDevice], now: Instant) {
+        let old: HashMap<String, u64> = self
+            .devices
+            .iter()
+            .filter_map(|d| Some((d.key(), d.urb_count?)))
+            .collect();
+        for device in new_devices {
+            let key = device.key();
+            if let (Some(count), Some(&before)) = (device.urb_count, old.get(&key)) {
+                if count > before {
+                    self.last_active.insert(key, now);
+                }
+            }
+        }
+        self.last_active
+            .retain(|_, at| now.duration_since(*at) < ACTIVITY_WINDOW);
+    }
+
+    /// Whether the device has seen traffic recently. Always false where
+    /// the kernel doesn't expose `urbnum` (and on the lsusb backend).
+    fn is_active(&self, device: &UsbDevice) -> bool {
+        self.last_active
+            .get(&device.key())
+            .is_some_and(|at| at.elapsed() < ACTIVITY_WINDOW)
+    }
+
+    /// Remember when each device appeared or vanished, for flap detection
+    fn record_transitions(&mut self, new_devices: &[UsbDevice], now: Instant) {
+        if self.stats.refresh_count == 0 {
+            return;
+        }
+        let window = Duration::from_secs(self.config.flap_window_secs);
+        record_transitions(
+            &mut self.transitions,
+            &self.devices,
+            new_devices,
+            now,
+            window,
+        );
+    }
+
+    fn is_flapping(&self, device: &UsbDevice) -> bool {
+        self.transitions
+            .get(&device.flap_key())
+            .is_some_and(|times| times.len() > self.config.flap_threshold)
+    }
+
+    /// Desktop notification for a DFU arrival, at most once per
+    /// NOTIFY_DEBOUNCE per device so a flapping bootloader can't spam
+    fn notify_dfu(&mut self, device: &UsbDevice) {
+        let now = Instant::now();
+        self.notified_at
+            .retain(|_, at| now.duration_since(*at) < NOTIFY_DEBOUNCE);
+        if self.notified_at.contains_key(&device.identity()) {
+            return;
+        }
+        self.notified_at.insert(device.identity(), now);
+
+        let body = format!("DFU device ready: {}", device.name);
+        // Talking to the notification daemon can block, keep it off the UI thread
+        thread::spawn(move || {
+            let shown = notify_rust::Notification::new()
+                .summary("cursed-usb")
+                .body(&body)
+                .show();
+            if let Err(err) = shown {
+                tracing::warn!(error = %err, "notification_failed");
+            }
+        });
+    }
+
+    fn push_log(&mut self, entry: LogEntry) {
+        if self.log.len() >= LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(entry);
+    }
+
+    /// Hiding the panel drops the `KernelLog`, which kills its journalctl;
+    /// the next show starts a fresh one
+    fn toggle_kernel_log(&mut self) {
+        self.show_kernel_log = !self.show_kernel_log;
+        self.kernel_log = self.show_kernel_log.then(KernelLog::spawn);
+    }
+
+    fn toggle_log(&mut self) {
+        self.show_log = !self.show_log;
+    }
+
+    /// Connected devices counted per vendor, most common first. Keyed by the
+    /// usb.ids name, then iManufacturer, then the bare VID.
+    fn vendor_summary(&self) -> Vec<(String, String, usize)> {
+        let mut counts: HashMap<String, (String, usize)> = HashMap::new();
+        for device in &self.devices {
+            let name = device
+                .vendor_name
+                .clone()
+                .or_else(|| device.manufacturer.clone())
+                .unwrap_or_else(|| device.vendor_id.clone());
+            counts
+                .entry(name)
+                .or_insert_with(|| (device.vendor_id.clone(), 0))
+                .1 += 1;
+        }
+        let mut summary: Vec<(String, String, usize)> = counts
+            .into_iter()
+            .map(|(name, (vendor_id, count))| (name, vendor_id, count))
+            .collect();
+        summary.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        summary
+    }
+
+    /// Point the list cursor back at `selected_key` within the visible rows
+    fn sync_selection(&mut self) {
+        let visible = self.visible_devices();
+        if visible.is_empty() {
+            // Keep selected_key so the selection comes back when the filter clears
+            self.list_state.select(None);
+            return;
+        }
+
+        if let Some(ref key) = self.selected_key {
+            if let Some(idx) = visible.iter().position(|d| d.key() == *key) {
+                self.list_state.select(Some(idx));
+            } else {
+                // Device gone or filtered out, keep index if valid
+                let current = self.list_state.selected().unwrap_or(0);
+                let new_idx = current.min(visible.len() - 1);
+                let new_key = visible[new_idx].key();
+                self.list_state.select(Some(new_idx));
+                self.selected_key = Some(new_key);
+            }
+        } else {
+            let new_key = visible[0].key();
+            self.list_state.select(Some(0));
+            self.selected_key = Some(new_key);
+        }
+    }
+
+    /// Case-insensitive substring match against name, IDs, and tty
+    fn matches_filter(&self, device: &UsbDevice) -> bool {
+        if self.filter_query.is_empty() {
+            return true;
+        }
+        if let Some(prefix) = self.filter_query.strip_prefix('@') {
+            return prefix.is_empty() || device.is_under_port(prefix);
+        }
+        match &self.filter_regex {
+            Some(Ok(regex)) => {
+                let haystack = format!("{} {} {}", device.name, device.id(), device.display_path());
+                return regex.is_match(&haystack);
+            }
+            // A half-typed pattern shouldn't blank the list
+            Some(Err(_)) => return true,
+            None => {}
+        }
+        let query = self.filter_query.to_lowercase();
+        device.name.to_lowercase().contains(&query)
+            || device.vendor_id.to_lowercase().contains(&query)
+            || device.product_id.to_lowercase().contains(&query)
+            || device
+                .tty_paths
+                .iter()
+                .any(|tty| tty.to_lowercase().contains(&query))
+    }
+
+    /// Whether any view filter is hiding devices
+    fn is_filtered(&self) -> bool {
+        self.dfu_only || self.tty_filter != TtyFilter::All || !self.filter_query.is_empty()
+    }
+
+    /// Root hubs and anything in the config's `ignore` list
+    fn is_internal(&self, device: &UsbDevice) -> bool {
+        device.vendor_id.eq_ignore_ascii_case("1d6b")
+            || self
+                .config
+                .ignore
+                .iter()
+                .any(|pattern| pattern.matches(device))
+    }
+
+    /// Left out of the list and the header count, though still tracked in the stats
+    fn is_hidden(&self, device: &UsbDevice) -> bool {
+        self.hide_internal && self.is_internal(device)
+    }
+
+    /// Whether a device gets through every view filter
+    fn is_listed(&self, device: &UsbDevice) -> bool {
+        (!self.dfu_only || device.is_dfu)
+            && self.tty_filter.matches(device)
+            && !self.is_hidden(device)
+            && self.matches_filter(device)
+    }
+
+    /// Devices shown in the list, in display order
+    fn visible_devices(&self) -> Vec<&UsbDevice> {
+        let mut visible: Vec<&UsbDevice> =
+            self.devices.iter().filter(|d| self.is_listed(d)).collect();
+        if self.tree_view {
+            // Depth-first by topology, skipping anything under a folded hub
+            visible.retain(|d| !self.is_folded_away(d));
+            visible.sort_by_key(|d| (d.bus.parse::<u32>().unwrap_or(0), d.port_path.clone()));
+        } else if self.group_duplicates {
+            // Pull identical devices up to the first of them; a folded group shows only that one
+            let mut first: HashMap<String, usize> = HashMap::new();
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for (index, device) in visible.iter().enumerate() {
+                first.entry(device.id()).or_insert(index);
+                *counts.entry(device.id()).or_default() += 1;
+            }
+            visible.sort_by_key(|d| first[&d.id()]);
+            let mut heads = HashSet::new();
+            visible.retain(|d| {
+                let id = d.id();
+                counts[&id] == 1 || self.expanded_groups.contains(&id) || heads.insert(id)
+            });
+        }
+        visible
+    }
+
+    /// How many listed devices share this one's VID:PID
+    fn group_size(&self, device: &UsbDevice) -> usize {
+        self.devices
+            .iter()
+            .filter(|d| self.is_listed(d))
+            .filter(|d| d.id() == device.id())
+            .count()
+    }
+
+    fn toggle_grouping(&mut self) {
+        self.group_duplicates = !self.group_duplicates;
+        if self.group_duplicates && self.tree_view {
+            self.set_status("Grouping is off in the tree view", self.theme.warn);
+        }
+        self.sync_selection();
+    }
+
+    /// Whether some hub above this device is collapsed
+    fn is_folded_away(&self, device: &UsbDevice) -> bool {
+        let bus = device.bus.parse::<u32>().unwrap_or(0);
+        (0..device.port_path.len()).any(|depth| {
+            let ancestor = if depth == 0 {
+                bus.to_string()
+            } else {
+                let ports: Vec<String> = device.port_path[..depth]
+                    .iter()
+                    .map(|port| port.to_string())
+                    .collect();
+                format!("{}-{}", bus, ports.join("."))
+            };
+            self.collapsed.contains(&ancestor)
+        })
+    }
+
+    /// Whether anything is plugged into this device
+    fn has_children(&self, device: &UsbDevice) -> bool {
+        let path = device.port_path_string();
+        self.devices
+            .iter()
+            .any(|d| d.parent_port_path_string().as_deref() == Some(path.as_str()))
+    }
+
+    fn toggle_tree(&mut self) {
+        self.tree_view = !self.tree_view;
+        if self.tree_view && self.backend == Backend::Lsusb {
+            self.set_status(
+                "No topology from lsusb, the tree is flat".to_string(),
+                self.theme.warn,
+            );
+        }
+        self.sync_selection();
+    }
+
+    /// Fold or unfold the hub under the cursor (None toggles). Collapsing a
+    /// leaf moves the cursor up to its hub instead.
+    fn set_expanded(&mut self, expand: Option<bool>) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        if self.group_duplicates && !self.tree_view {
+            let id = device.id();
+            if self.group_size(&device) < 2 {
+                return;
+            }
+            let expand = expand.unwrap_or_else(|| !self.expanded_groups.contains(&id));
+            if expand {
+                self.expanded_groups.insert(id);
+            } else {
+                self.expanded_groups.remove(&id);
+                // Land on the row that stands for the whole group
+                let head = self.visible_devices().iter().position(|d| d.id() == id);
+                if let Some(index) = head {
+                    self.select_visible(index);
+                }
+            }
+            self.sync_selection();
+            return;
+        }
+        if !self.tree_view {
+            return;
+        }
+        let path = device.port_path_string();
+        if !self.has_children(&device) {
+            if expand == Some(false) {
+                if let Some(parent) = device.parent_port_path_string() {
+                    let hub = self
+                        .visible_devices()
+                        .iter()
+                        .position(|d| d.port_path_string() == parent);
+                    if let Some(index) = hub {
+                        self.select_visible(index);
+                    }
+                }
+            }
+            return;
+        }
+        let expand = expand.unwrap_or_else(|| self.collapsed.contains(&path));
+        if expand {
+            self.collapsed.remove(&path);
+        } else {
+            self.collapsed.insert(path);
+        }
+        self.sync_selection();
+    }
+
+    fn toggle_dfu_only(&mut self) {
+        self.dfu_only = !self.dfu_only;
+        self.sync_selection();
+    }
+
+    /// All → tty only → no tty. The device selected before a mode hid it
+    /// comes back once a later mode shows it, unless the cursor moved since.
+    fn cycle_tty_filter(&mut self) {
+        self.tty_filter = self.tty_filter.next();
+        let wanted = self
+            .tty_filter_anchor
+            .take()
+            .or_else(|| self.selected_key.clone());
+        self.selected_key = wanted.clone();
+        self.sync_selection();
+        if self.selected_key != wanted {
+            self.tty_filter_anchor = wanted;
+        }
+    }
+
+    /// Keep the selection on whichever device owns the --highlight tty now,
+    /// through replugs and renumbering, until the user moves off it
+    fn track_highlight(&mut self) {
+        let Some(tty) = &self.highlight_tty else {
+            return;
+        };
+        let owner = self
+            .devices
+            .iter()
+            .find(|d| d.tty_paths.contains(tty))
+            .map(|d| d.key());
+        match owner {
+            Some(owner) if self.highlight_pinned => self.selected_key = Some(owner),
+            Some(_) => {}
+            None if self.stats.refresh_count == 0 => {
+                let message = format!("No device owns {} yet", tty);
+                self.set_status(message, self.theme.warn);
+            }
+            None => {}
+        }
+    }
+
+    /// The --highlight tty, if this device owns it
+    fn highlighted_tty(&self, device: &UsbDevice) -> Option<&str> {
+        self.highlight_tty
+            .as_deref()
+            .filter(|tty| device.tty_paths.iter().any(|path| path == tty))
+    }
+
+    fn toggle_follow_new(&mut self) {
+        self.follow_new = !self.follow_new;
+        let message = if self.follow_new {
+            "Following new devices"
+        } else {
+            "Stopped following new devices"
+        };
+        self.set_status(message, self.theme.accent);
+    }
+
+    fn toggle_hide_internal(&mut self) {
+        self.hide_internal = !self.hide_internal;
+        self.config.hide_internal = self.hide_internal;
+        self.sync_selection();
+        let state = if self.hide_internal {
+            "hidden"
+        } else {
+            "shown"
+        };
+        match Config::save_key("hide_internal", self.hide_internal) {
+            Ok(()) => self.set_status(format!("Internal devices {}", state), self.theme.accent),
+            Err(err) => self.set_status(
+                format!("Internal devices {} (not saved: {})", state, err),
+                self.theme.warn,
+            ),
+        }
+    }
+
+    /// Devices counted in the header: everything not hidden as internal
+    fn shown_count(&self) -> usize {
+        self.devices.iter().filter(|d| !self.is_hidden(d)).count()
+    }
+
+    fn toggle_vendor_colors(&mut self) {
+        self.vendor_colors = !self.vendor_colors;
+        let state = if self.vendor_colors { "on" } else { "off" };
+        self.set_status(format!("Vendor colors {}", state), self.theme.accent);
+    }
+
+    /// Show the selected device's sysfs directory in $FILE_MANAGER, or
+    /// xdg-open without one. Detached, with its output kept off the TUI.
+    fn open_sysfs_dir(&mut self) {
+        let Some(device) = self.selected_device() else {
+            return;
+        };
+        let Some(dir) = device.locate_sysfs_dir() else {
+            self.set_status(
+                format!("No sysfs directory for {}", device.name),
+                self.theme.warn,
+            );
+            return;
+        };
+        let opener = std::env::var("FILE_MANAGER")
+            .ok()
+            .filter(|opener| !opener.is_empty())
+            .unwrap_or_else(|| "xdg-open".to_string());
+        let spawned = Command::new(&opener)
+            .arg(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                // Reap it whenever it exits rather than leaving a zombie around
+                thread::spawn(move || child.wait());
+                self.set_status(
+                    format!("Opened {} with {}", dir.display(), opener),
+                    self.theme.good,
+                );
+            }
+            Err(err) => self.set_status(
+                format!("Couldn't run {} ({}): {}", opener, err, dir.display()),
+                self.theme.bad,
+            ),
+        }
+    }
+
+    /// Start an --on-connect/--on-disconnect command for `device` and leave it
+    /// running; like the file manager, its output stays off the TUI
+    fn run_hook(&mut self, template: &str, device: &UsbDevice) {
+        let command = expand_hook(template, device);
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                tracing::debug!(command = %command, "hook_started");
+                thread::spawn(move || child.wait());
+            }
+            Err(err) => {
+                tracing::warn!(command = %command, error = %err, "hook_failed");
+                self.set_status(format!("Couldn't run hook: {}", err), self.theme.bad);
+            }
+        }
+    }
+
+    /// Open the note box for the selected device, prefilled with its current note
+    fn open_note_editor(&mut self) {
+        if let Some(warning) = &self.notes_warning {
+            // Saving would replace the user's file with just this one note
+            self.set_status(
+                format!("Notes are read-only until it's fixed: {}", warning),
+                self.theme.bad,
+            );
+            return;
+        }
+        let Some(device) = self.selected_device() else {
+            return;
+        };
+        self.note_editor = Some(NoteEditor {
+            identity: device.identity(),
+            name: device.name.clone(),
+            shared: device.serial.is_none(),
+            text: self.notes.get(device).unwrap_or_default().to_string(),
+        });
+    }
+
+    /// Store the typed note (an empty one deletes it) and write notes.toml
+    fn save_note(&mut self) {
+        let Some(editor) = self.note_editor.take() else {
+            return;
+        };
+        let text = editor.text.trim();
+        let message = if text.is_empty() {
+            self.notes.0.remove(&editor.identity);
+            format!("Removed note from {}", editor.name)
+        } else {
+            self.notes.0.insert(editor.identity, text.to_string());
+            format!("Noted {}", editor.name)
+        };
+        match self.notes.save() {
+            Ok(()) => self.set_status(message, self.theme.good),
+            Err(err) => self.set_status(format!("Couldn't save notes: {}", err), self.theme.bad),
+        }
+    }
+
+    fn start_search(&mut self) {
+        self.searching = true;
+    }
+
+    /// Leave search mode but keep the query applied
+    fn finish_search(&mut self) {
+        self.searching = false;
+    }
+
+    fn clear_search(&mut self) {
+        self.searching = false;
+        self.filter_query.clear();
+        self.filter_changed();
+    }
+
+    fn push_search_char(&mut self, c: char) {
+        self.filter_query.push(c);
+        self.filter_changed();
+    }
+
+    fn pop_search_char(&mut self) {
+        self.filter_query.pop();
+        self.filter_changed();
+    }
+
+    /// Recompile the regex for "~pattern" queries and re-point the selection
+    fn filter_changed(&mut self) {
+        self.filter_regex = self.filter_query.strip_prefix('~').map(Regex::new);
+        self.sync_selection();
+    }
+
+    fn try_receive_kernel_log(&mut self) {
+        let Some(log) = &mut self.kernel_log else {
+            return;
+        };
+        while let Ok(message) = log.receiver.try_recv() {
+            match message {
+                KernelMessage::Line(line) => {
+                    if log.lines.len() == LOG_CAPACITY {
+                        log.lines.pop_front();
+                    }
+                    log.lines.push_back(line);
+                }
+                KernelMessage::Unavailable(reason) => log.unavailable = Some(reason),
+            }
+        }
+    }
+
+    fn try_receive_devices(&mut self) {
+        self.stats.triggers = self.poller.trigger_counts();
+        self.stats.effective_interval = self.poller.current_interval();
+        // Non-blocking receive - only take the latest update
+        let mut latest: Option<PollResult> = None;
+        while let Ok(update) = self.device_receiver.try_recv() {
+            latest = Some(update);
+        }
+        let Some((devices, duration, started)) = latest else {
+            return;
+        };
+        if !self.paused || self.apply_next_update {
+            self.apply_next_update = false;
+            self.update_devices(devices, duration, started);
+        }
+    }
+
+    /// Reset the cumulative stats, on disk too
+    fn clear_stats(&mut self) {
+        self.stats.clear_cumulative();
+        // Current devices still count as seen
+        for device in &self.devices {
+            self.stats.devices_ever_seen.insert(device.id());
+            if device.is_dfu {
+                self.stats.dfu_devices_ever_seen.insert(device.id());
+            }
+        }
+        let removed = if self.persist_stats {
+            PersistedStats::remove()
+        } else {
+            Ok(())
+        };
+        match removed {
+            Ok(()) => self.set_status("Cleared stats", self.theme.good),
+            Err(err) => self.set_status(format!("Couldn't clear stats: {}", err), self.theme.bad),
+        }
+    }
+
+    fn save_stats(&self) -> Result<()> {
+        if self.persist_stats {
+            self.stats.to_persisted().save()?;
+        }
+        Ok(())
+    }
+
+    /// Number keys isolate a bus; the same number again (or 0) shows them all
+    fn toggle_bus(&mut self, bus: u32) {
+        self.bus_filter = (bus != 0 && self.bus_filter != Some(bus)).then_some(bus);
+        // Hidden buses aren't disconnects, and revealed ones aren't connects
+        self.devices.retain(|d| on_bus(d, self.bus_filter));
+        self.rescoped = true;
+        self.sync_selection();
+        self.manual_refresh();
+    }
+
+    fn manual_refresh(&mut self) {
+        if self.paused {
+            self.apply_next_update = true;
+        }
+        self.poller.refresh();
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.apply_next_update = false;
+    }
+
+    fn set_status(&mut self, text: impl Into<String>, color: Color) {
+        self.status = Some(StatusMessage {
+            text: text.into(),
+            color,
+            shown_at: Instant::now(),
+        });
+    }
+
+    /// The footer message, if one is still fresh
+    fn current_status(&self) -> Option<&StatusMessage> {
+        self.status
+            .as_ref()
+            .filter(|status| status.shown_at.elapsed() < STATUS_TIMEOUT)
+    }
+
+    fn export_devices(&mut self) {
+        match export_json(&self.devices) {
+            Ok(path) => self.set_status(format!("Wrote {}", path), self.theme.good),
+            Err(err) => self.set_status(format!("Export failed: {}", err), self.theme.bad),
+        }
+    }
+
+    fn export_devices_csv(&mut self) {
+        match export_csv(&self.devices) {
+            Ok(path) => self.set_status(format!("Wrote {}", path), self.theme.good),
+            Err(err) => self.set_status(format!("Export failed: {}", err), self.theme.bad),
+        }
+    }
+
+    /// Queue dfu-util for the DFU-mode targets, pending confirmation
+    fn request_flash(&mut self) {
+        let targets = self.action_targets();
+        if targets.is_empty() {
+            return;
+        }
+        let (dfu, not_dfu): (Vec<UsbDevice>, Vec<UsbDevice>) =
+            targets.into_iter().partition(|d| d.is_dfu);
+        if dfu.is_empty() {
+            let message = match not_dfu.as_slice() {
+                [device] => format!("{} is not in DFU mode", device.name),
+                _ => "None of the selected devices are in DFU mode".to_string(),
+            };
+            self.set_status(message, self.theme.warn);
+            return;
+        }
+        self.pending = Some(PendingAction::Flash {
+            devices: dfu,
+            skipped: not_dfu.len(),
+        });
+    }
+
+    /// Queue a port reset of the targets, pending confirmation
+    fn request_reset(&mut self) {
+        let targets = self.action_targets();
+        if !targets.is_empty() {
+            self.pending = Some(PendingAction::Reset(targets));
+        }
+    }
+
+    fn reset_devices(&mut self, targets: &[UsbDevice]) {
+        let mut failed = vec![];
+        for device in targets {
+            if let Err(err) = reset_device(&device.dev_path) {
+                failed.push((device, err));
+            }
+        }
+
+        match (targets, failed.as_slice()) {
+            (_, []) if targets.len() > 1 => {
+                self.set_status(format!("Reset {} devices", targets.len()), self.theme.good)
+            }
+            ([device], []) => self.set_status(format!("Reset {}", device.name), self.theme.good),
+            (_, [(device, err)]) => self.set_status(
+                format!("Reset {} failed: {}", device.name, describe_io_error(err)),
+                self.theme.bad,
+            ),
+            (_, [(_, err), ..]) => self.set_status(
+                format!(
+                    "Reset failed on {} of {}: {}",
+                    failed.len(),
+                    targets.len(),
+                    describe_io_error(err)
+                ),
+                self.theme.bad,
+            ),
+            _ => {}
+        }
+    }
+
+    /// Unmount and power off the selected mass storage device. Ejecting is
+    /// undone by replugging, so unlike flash and reset there's no prompt.
+    /// udisksctl waits on D-Bus, so it runs on a thread.
+    fn eject(&mut self) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        if self.eject_job.is_some() {
+            self.set_status("Already ejecting a device", self.theme.warn);
+            return;
+        }
+        if self.backend == Backend::Lsusb {
+            // No interface classes from lsusb, so no way to tell
+            self.set_status("Eject needs the sysfs backend", self.theme.warn);
+            return;
+        }
+        if !device.is_mass_storage() {
+            self.set_status(
+                format!("{} is not a mass storage device", device.name),
+                self.theme.warn,
+            );
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let name = device.name.clone();
+        thread::spawn(move || {
+            let _ = sender.send(eject_device(&device));
+        });
+        self.eject_job = Some((name, receiver));
+    }
+
+    fn try_receive_eject(&mut self) {
+        let Some((name, receiver)) = &self.eject_job else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err(std::io::Error::other("eject thread died"))
+            }
+        };
+        let name = name.clone();
+        self.eject_job = None;
+        self.quit_during_eject = false;
+        match result {
+            Ok(0) => self.set_status(format!("Ejected {}", name), self.theme.good),
+            Ok(unmounted) => self.set_status(
+                format!("Unmounted {} and ejected {}", unmounted, name),
+                self.theme.good,
+            ),
+            Err(err) => self.set_status(
+                format!("Eject {} failed: {}", name, describe_io_error(&err)),
+                self.theme.bad,
+            ),
+        }
+    }
+
+    /// Flip the selected device's sysfs `authorized` flag. Deauthorizing
+    /// drops its drivers, so it waits for confirmation; pressing again
+    /// brings them back straight away.
+    fn toggle_authorized(&mut self) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        match device.authorized {
+            None => self.set_status("Authorization needs the sysfs backend", self.theme.warn),
+            Some(true) => self.pending = Some(PendingAction::Deauthorize(Box::new(device))),
+            Some(false) => self.apply_authorized(&device, true),
+        }
+    }
+
+    fn apply_authorized(&mut self, device: &UsbDevice, authorized: bool) {
+        match set_authorized(device, authorized) {
+            Ok(()) => {
+                let verb = if authorized {
+                    "Authorized"
+                } else {
+                    "Deauthorized"
+                };
+                self.set_status(format!("{} {}", verb, device.name), self.theme.good);
+                self.manual_refresh();
+            }
+            Err(err) => self.set_status(
+                format!(
+                    "Authorizing {} failed: {}",
+                    device.name,
+                    describe_io_error(&err)
+                ),
+                self.theme.bad,
+            ),
+        }
+    }
+
+    /// Queue detaching the drivers from the cursor device's interfaces,
+    /// pending confirmation
+    fn request_unbind(&mut self) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        match bound_interfaces(&device) {
+            Ok(interfaces) if interfaces.is_empty() => self.set_status(
+                format!("No driver is bound to {}", device.name),
+                self.theme.warn,
+            ),
+            Ok(interfaces) => {
+                self.pending = Some(PendingAction::Unbind {
+                    device: Box::new(device),
+                    interfaces,
+                })
+            }
+            Err(_) => self.set_status("Unbinding needs sysfs", self.theme.warn),
+        }
+    }
+
+    fn unbind_drivers(&mut self, device: &UsbDevice, interfaces: &[BoundInterface]) {
+        let failed = interfaces
+            .iter()
+            .find_map(|bound| unbind_driver(bound).err().map(|err| (bound, err)));
+        match failed {
+            None => self.set_status(
+                format!("Unbound {} from {}", driver_list(interfaces), device.name),
+                self.theme.good,
+            ),
+            Some((bound, err)) => {
+                // Writing to unbind is root-only; a udev rule doesn't help here
+                let reason = if err.kind() == std::io::ErrorKind::PermissionDenied {
+                    format!(
+                        "permission denied writing /sys/bus/usb/drivers/{}/unbind (run as root)",
+                        bound.driver
+                    )
+                } else {
+                    describe_io_error(&err)
+                };
+                self.set_status(
+                    format!(
+                        "Unbinding {} from {} failed: {}",
+                        bound.driver, bound.interface, reason
+                    ),
+                    self.theme.bad,
+                );
+            }
+        }
+        self.manual_refresh();
+    }
+
+    /// Devices an action applies to: the multi-selection if any, else the cursor row
+    fn action_targets(&self) -> Vec<UsbDevice> {
+        if self.selected_keys.is_empty() {
+            return self.selected_device().cloned().into_iter().collect();
+        }
+        self.devices
+            .iter()
+            .filter(|d| self.selected_keys.contains(&d.key()))
+            .cloned()
+            .collect()
+    }
+
+    fn toggle_marked(&mut self) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        let id = device.id();
+        let folded_group = self.group_duplicates
+            && !self.tree_view
+            && !self.expanded_groups.contains(&id)
+            && self.group_size(&device) > 1;
+        if !folded_group {
+            let key = device.key();
+            if !self.selected_keys.remove(&key) {
+                self.selected_keys.insert(key);
+            }
+            return;
+        }
+        // A folded group row marks or unmarks every unit in it
+        let keys: Vec<String> = self
+            .devices
+            .iter()
+            .filter(|d| d.id() == id && self.is_listed(d))
+            .map(|d| d.key())
+            .collect();
+        if keys.iter().all(|key| self.selected_keys.contains(key)) {
+            for key in &keys {
+                self.selected_keys.remove(key);
+            }
+        } else {
+            self.selected_keys.extend(keys);
+        }
+    }
+
+    fn clear_marked(&mut self) {
+        self.selected_keys.clear();
+    }
+
+    fn copy_selected_path(&mut self) {
+        if let Some(path) = self.selected_device().map(|d| d.display_path().to_string()) {
+            self.copy_text(path);
+        }
+    }
+
+    fn copy_selected_arg(&mut self, arg: impl Fn(&UsbDevice) -> String) {
+        if let Some(text) = self.selected_device().map(arg) {
+            self.copy_text(text);
+        }
+    }
+
+    /// Copy to the system clipboard, falling back to an OSC 52 escape when
+    /// there's no clipboard (SSH, headless)
+    fn copy_text(&mut self, text: String) {
+        if self.clipboard.is_none() {
+            self.clipboard = arboard::Clipboard::new()
+                .inspect_err(|err| tracing::debug!(error = %err, "clipboard_unavailable"))
+                .ok();
+        }
+        let copied = self
+            .clipboard
+            .as_mut()
+            .is_some_and(|clipboard| clipboard.set_text(text.clone()).is_ok());
+
+        if copied {
+            self.set_status(format!("Copied {}", text), self.theme.good);
+        } else {
+            match copy_osc52(&text) {
+                Ok(()) => self.set_status(format!("Copied {} (OSC 52)", text), self.theme.good),
+                Err(err) => self.set_status(format!("Copy failed: {}", err), self.theme.bad),
+            }
+        }
+    }
+
+    fn selected_device(&self) -> Option<&UsbDevice> {
+        self.list_state
+            .selected()
+            .and_then(|i| self.visible_devices().get(i).copied())
+    }
+
+    fn next(&mut self) {
+        let visible = self.visible_devices();
+        if visible.is_empty() {
+            return;
+        }
+        let i = match self.list_state.selected() {
+            Some(i) => {
+                if i >= visible.len() - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.select_visible(i);
+    }
+
+    fn previous(&mut self) {
+        let visible = self.visible_devices();
+        if visible.is_empty() {
+            return;
+        }
+        let i = match self.list_state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    visible.len() - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.select_visible(i);
+    }
+
+    /// Jump a screenful of rows down (positive) or up, stopping at the ends
+    fn page(&mut self, direction: isize) {
+        // Rows last drawn, across both panes in the two-column layout
+        let page = self
+            .list_panes
+            .iter()
+            .map(|(area, _)| area.height.saturating_sub(2) as usize)
+            .sum::<usize>()
+            .max(1);
+        self.move_by(direction * page as isize);
+    }
+
+    /// Move `rows` down (positive) or up in one step, stopping at the ends
+    /// rather than wrapping like a single j/k does; for `5j` and paging
+    fn move_by(&mut self, rows: isize) {
+        let len = self.visible_devices().len();
+        if len == 0 {
+            return;
+        }
+        let current = self.list_state.selected().unwrap_or(0);
+        self.select_visible(current.saturating_add_signed(rows).min(len - 1));
+    }
+
+    /// Jump to a 1-based row, clamped to the list, for `5G` and `5gg`
+    fn goto_row(&mut self, row: u32) {
+        let last = self.visible_devices().len().saturating_sub(1);
+        self.select_visible((row as usize).saturating_sub(1).min(last));
+    }
+
+    /// Move the cursor to a row of the visible list. Only the user does
+    /// this, so it ends follow mode rather than fight over the cursor.
+    fn select_visible(&mut self, index: usize) {
+        if self.follow_new {
+            self.follow_new = false;
+            self.set_status("Stopped following new devices", self.theme.muted);
+        }
+        let target = self
+            .visible_devices()
+            .get(index)
+            .map(|d| (d.key(), self.highlighted_tty(d).is_some()));
+        if let Some((key, highlighted)) = target {
+            self.highlight_pinned = highlighted;
+            self.tty_filter_anchor = None;
+            self.list_state.select(Some(index));
+            self.selected_key = Some(key);
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.next(),
+            MouseEventKind::ScrollUp => self.previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                // Rows start inside the list border
+                let position = Position::new(mouse.column, mouse.row);
+                let hit = self.list_panes.iter().find_map(|(area, first)| {
+                    let rows = area.inner(Margin::new(1, 1));
+                    rows.contains(position)
+                        .then(|| first + (mouse.row - rows.y) as usize)
+                });
+                if let Some(index) = hit {
+                    self.select_visible(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dfu_count(&self) -> usize {
+        self.devices.iter().filter(|d| d.is_dfu).count()
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if color_disabled(&cli) {
+        color_eyre::config::HookBuilder::new()
+            .theme(color_eyre::config::Theme::new())
+            .install()?;
+    } else {
+        color_eyre::install()?;
+    }
+
+    let headless = cli.command.is_some()
+        || cli.watch_json
+        || cli.once
+        || cli.bench.is_some()
+        || cli.log_file.is_some()
+        || cli.wait_for.is_some();
+    init_tracing(&cli, headless)?;
+
+    let (config, config_warning) = Config::load();
+    // The TUI shows it in its footer instead
+    if headless {
+        if let Some(warning) = &config_warning {
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    if let Some(command) = &cli.command {
+        return run_device_command(&cli, &config, command);
+    }
+    if cli.watch_json {
+        return watch_json(&cli, &config);
+    }
+    if cli.once {
+        return print_once(&cli, &config);
+    }
+    if let Some(runs) = cli.bench {
+        bench(&cli, &config, runs);
+        return Ok(());
+    }
+    if let Some(path) = &cli.log_file {
+        return log_to_file(&cli, &config, path);
+    }
+    if let Some(target) = &cli.wait_for {
+        return wait_for(&cli, &config, target);
+    }
+
+    install_panic_hook(restore_terminal);
+    let terminal = init_terminal()?;
+    let result = run(terminal, &cli, config, config_warning);
+    restore_terminal();
+    result
+}
+
+/// Structured logging, off unless --log-level or RUST_LOG asks for it. JSON
+/// either way; the TUI owns the terminal, so it gets a file instead of stderr.
+fn init_tracing(cli: &Cli, headless: bool) -> Result<()> {
+    let filter = match cli.log_level {
+        Some(level) => EnvFilter::new(level.directive()),
+        None => match EnvFilter::try_from_default_env() {
+            Ok(filter) => filter,
+            Err(_) => return Ok(()),
+        },
+    };
+    let writer = if headless {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        let Some(path) = tracing_log_path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .wrap_err_with(|| format!("couldn't open {}", path.display()))?;
+        BoxMakeWriter::new(std::sync::Mutex::new(file))
+    };
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .init();
+    Ok(())
+}
+
+/// Where the TUI's tracing output goes, next to the persisted stats
+fn tracing_log_path() -> Option<PathBuf> {
+    PersistedStats::path().map(|path| path.with_file_name("cursed-usb.log"))
+}
+
+/// A connect or disconnect as a tracing event
+fn trace_transition(connected: bool, device: &UsbDevice) {
+    let (id, key, name) = (device.id(), device.key(), device.name.as_str());
+    if connected {
+        tracing::info!(%id, %key, name, dfu = device.is_dfu, "device_connected");
+    } else {
+        tracing::info!(%id, %key, name, dfu = device.is_dfu, "device_disconnected");
+    }
+}
+
+/// lsusb invocation from the config, with the CLI flags taking precedence
+fn lsusb_options(cli: &Cli, config: &Config) -> LsusbOptions {
+    LsusbOptions {
+        path: cli.lsusb_path.clone().or_else(|| config.lsusb_path.clone()),
+        sudo: cli.lsusb_sudo || config.lsusb_sudo,
+        file: cli.lsusb_file.clone(),
+        skip_tty_scan: cli.no_tty_scan,
+    }
+}
+
+/// --backend, else the detected one; lsusb when replaying a captured listing
+fn backend(cli: &Cli) -> Backend {
+    if cli.lsusb_file.is_some() {
+        Backend::Lsusb
+    } else {
+        cli.backend.unwrap_or_else(Backend::detect)
+    }
+}
+
+/// Enter the TUI: raw mode, alternate screen, mouse capture
+fn init_terminal() -> Result<DefaultTerminal> {
+    let terminal = ratatui::init();
+    crossterm::execute!(std::io::stdout(), EnableMouseCapture)?;
+    Ok(terminal)
+}
+
+/// Leave the terminal the way we found it
+fn restore_terminal() {
+    let _ = crossterm::execute!(std::io::stdout(), DisableMouseCapture);
+    ratatui::restore();
+}
+
+/// Run `restore` ahead of whatever panic hook is already in place (color-eyre's),
+/// so a panic mid-render prints its backtrace to a sane shell rather than into
+/// a raw-mode alternate screen that still has mouse capture on
+fn install_panic_hook(restore: fn()) {
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        report(info);
+    }));
+}
+
+/// Headless mode: poll until a device matches, print its path and exit 0,
+/// or exit 1 once --timeout runs out
+fn wait_for(cli: &Cli, config: &Config, target: &WaitTarget) -> Result<()> {
+    let backend = backend(cli);
+    let usb_ids = load_usb_ids();
+    let lsusb = lsusb_options(cli, config);
+    let scope = IdScope::new(cli);
+    let interval = Duration::from_millis(cli.interval_ms);
+    let deadline = cli
+        .timeout
+        .map(|secs| Instant::now() + Duration::from_secs(secs));
+
+    loop {
+        let poll = get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb);
+        let matched = poll
+            .devices
+            .iter()
+            .filter(|d| on_bus(d, cli.bus) && scope.contains(d))
+            .find_map(|d| target.matched_path(d));
+        if let Some(path) = matched {
+            println!("{}", path);
+            return Ok(());
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            eprintln!("timed out waiting for a matching device");
+            std::process::exit(1);
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// `reset`/`tty` subcommands: act on every match, exit 1 if nothing matched
+/// or any reset failed
+fn run_device_command(cli: &Cli, config: &Config, command: &DeviceCommand) -> Result<()> {
+    let (DeviceCommand::Reset { id } | DeviceCommand::Tty { id }) = command;
+    let scope = IdScope::new(cli);
+    let poll = get_usb_devices(
+        backend(cli),
+        &load_usb_ids(),
+        &config.dfu,
+        &lsusb_options(cli, config),
+    );
+    let matches: Vec<&UsbDevice> = poll
+        .devices
+        .iter()
+        .filter(|d| d.id() == *id && on_bus(d, cli.bus) && scope.contains(d))
+        .collect();
+    if matches.is_empty() {
+        eprintln!("no device with ID {}", id);
+        std::process::exit(1);
+    }
+
+    let mut ok = true;
+    for device in matches {
+        match command {
+            DeviceCommand::Reset { .. } => match reset_device(&device.dev_path) {
+                Ok(()) => println!("reset {} ({})", device.dev_path, device.name),
+                Err(err) => {
+                    eprintln!(
+                        "reset {} failed: {}",
+                        device.dev_path,
+                        describe_io_error(&err)
+                    );
+                    ok = false;
+                }
+            },
+            DeviceCommand::Tty { .. } => {
+                for tty in &device.tty_paths {
+                    println!("{}", tty);
+                }
+                if device.tty_paths.is_empty() {
+                    eprintln!("{} ({}) has no tty", device.dev_path, device.name);
+                    ok = false;
+                }
+            }
+        }
+    }
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Headless mode: enumerate a single time and print the devices to stdout
+fn print_once(cli: &Cli, config: &Config) -> Result<()> {
+    let scope = IdScope::new(cli);
+    let poll = get_usb_devices(
+        backend(cli),
+        &load_usb_ids(),
+        &config.dfu,
+        &lsusb_options(cli, config),
+    );
+    for warning in &poll.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let mut devices = poll.devices;
+    devices.retain(|d| on_bus(d, cli.bus) && scope.contains(d));
+    let output = match cli.format {
+        OutputFormat::Json => devices_json(&devices)? + "\n",
+        OutputFormat::Csv => devices_csv(&devices),
+        OutputFormat::Table => {
+            let header = ["NAME", "ID", "BUS", "DEV", "TTY", "DFU"].map(String::from);
+            let rows: Vec<[String; 6]> = std::iter::once(header)
+                .chain(devices.iter().map(once_row))
+                .collect();
+            let mut widths = [0; 6];
+            for row in &rows {
+                for (width, field) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(field.chars().count());
+                }
+            }
+            let mut out = String::new();
+            for row in &rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(widths)
+                    .map(|(field, width)| format!("{:<width$}", field, width = width))
+                    .collect();
+                out.push_str(cells.join("  ").trim_end());
+                out.push('\n');
+            }
+            out
+        }
+    };
+    let mut stdout = std::io::stdout().lock();
+    // A closed pipe (e.g. `| head`) is not an error worth reporting
+    let _ = stdout
+        .write_all(output.as_bytes())
+        .and_then(|_| stdout.flush());
+    Ok(())
+}
+
+/// --bench: time `runs` enumerations per backend and print min/median/p95/max,
+/// a histogram, and an interval that keeps scanning a small share of the time
+fn bench(cli: &Cli, config: &Config, runs: u32) {
+    let usb_ids = load_usb_ids();
+    let lsusb = lsusb_options(cli, config);
+    let mut backends = vec![Backend::Lsusb];
+    if backend(cli) == Backend::Sysfs {
+        backends.insert(0, Backend::Sysfs);
+    }
+    #[cfg(feature = "rusb")]
+    backends.push(Backend::Rusb);
+
+    let mut medians = vec![];
+    for backend in backends {
+        // Warm-up, and a chance to find out the backend doesn't work here
+        let poll = get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb);
+        if poll.devices.is_empty() {
+            if let Some(warning) = poll.warnings.first() {
+                println!("{}: unavailable ({})\n", backend.name(), warning);
+                continue;
+            }
+        }
+
+        let mut samples: Vec<Duration> = (0..runs)
+            .map(|_| {
+                let start = Instant::now();
+                get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb);
+                start.elapsed()
+            })
+            .collect();
+        samples.sort();
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+        let ms = |d: Duration| format!("{:.2}ms", d.as_secs_f64() * 1000.0);
+        println!(
+            "{}: {} runs, {} devices  min {}  median {}  p95 {}  max {}",
+            backend.name(),
+            runs,
+            poll.devices.len(),
+            ms(samples[0]),
+            ms(percentile(50)),
+            ms(percentile(95)),
+            ms(samples[samples.len() - 1])
+        );
+        print_histogram(&samples);
+        println!();
+        medians.push((backend, percentile(50)));
+    }
+
+    medians.sort_by_key(|(_, median)| *median);
+    let Some(&(fastest, median)) = medians.first() else {
+        println!("No backend could enumerate devices");
+        return;
+    };
+    if let Some(&(slower, slower_median)) = medians.get(1) {
+        println!(
+            "{} is {:.1}x faster than {} here",
+            fastest.name(),
+            slower_median.as_secs_f64() / median.as_secs_f64().max(f64::EPSILON),
+            slower.name()
+        );
+    }
+    // Scanning at most ~5% of the time, within what --interval-ms accepts
+    let suggested = ((median.as_secs_f64() * 20_000.0).ceil() as u64).clamp(20, 10_000);
+    println!(
+        "--interval-ms {} keeps {} scanning ~5% of the time (now {}ms)",
+        suggested,
+        fastest.name(),
+        cli.interval_ms
+    );
+}
+
+/// Eight equal-width latency buckets from min to max, as bars
+fn print_histogram(sorted: &[Duration]) {
+    const BUCKETS: usize = 8;
+    const BAR_WIDTH: usize = 40;
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    let span = (max - min).as_secs_f64().max(f64::EPSILON);
+    let mut counts = [0usize; BUCKETS];
+    for sample in sorted {
+        let bucket = ((*sample - min).as_secs_f64() / span * BUCKETS as f64) as usize;
+        counts[bucket.min(BUCKETS - 1)] += 1;
+    }
+    let peak = counts.iter().copied().max().unwrap_or(1).max(1);
+    for (i, count) in counts.iter().enumerate() {
+        let low = min.as_secs_f64() + span * i as f64 / BUCKETS as f64;
+        println!(
+            "  {:>8.2}ms {:<width$} {}",
+            low * 1000.0,
+            "█".repeat(count * BAR_WIDTH / peak),
+            count,
+            width = BAR_WIDTH
+        );
+    }
+}
+
+/// The --bus / number-key filter; None lets every bus through
+fn on_bus(device: &UsbDevice, bus: Option<u32>) -> bool {
+    bus.is_none_or(|bus| device.bus.parse::<u32>().ok() == Some(bus))
+}
+
+/// The --filter-vid / --filter-pid scope, fixed for the session. Either
+/// list matches any of its IDs; the two AND together. Empty lets all through.
+#[derive(Debug, Clone, Default)]
+struct IdScope {
+    vids: Vec<String>,
+    pids: Vec<String>,
+}
+
+impl IdScope {
+    fn new(cli: &Cli) -> Self {
+        Self {
+            vids: cli.filter_vid.clone(),
+            pids: cli.filter_pid.clone(),
+        }
+    }
+
+    fn contains(&self, device: &UsbDevice) -> bool {
+        let matches = |ids: &[String], id: &str| {
+            ids.is_empty() || ids.iter().any(|known| known.eq_ignore_ascii_case(id))
+        };
+        matches(&self.vids, &device.vendor_id) && matches(&self.pids, &device.product_id)
+    }
+
+    /// "vid 0483", "vid 0483/1209 pid df11", or None when unscoped
+    fn label(&self) -> Option<String> {
+        let parts: Vec<String> = [("vid", &self.vids), ("pid", &self.pids)]
+            .into_iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(name, ids)| format!("{} {}", name, ids.join("/")))
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+/// The columns `--once` prints as a table
+fn once_row(device: &UsbDevice) -> [String; 6] {
+    [
+        device.name.clone(),
+        device.id(),
+        device.bus.clone(),
+        device.device.clone(),
+        device.tty_paths.join(", "),
+        if device.is_dfu { "yes" } else { "no" }.to_string(),
+    ]
+}
+
+/// A device's ttys for a one-line summary, "none" when it has none
+fn tty_list(device: &UsbDevice) -> String {
+    if device.tty_paths.is_empty() {
+        "none".to_string()
+    } else {
+        device.tty_paths.join(", ")
+    }
+}
+
+/// Headless mode: poll and stream connect/disconnect events as JSON lines until interrupted
+fn watch_json(cli: &Cli, config: &Config) -> Result<()> {
+    let backend = backend(cli);
+    let usb_ids = load_usb_ids();
+    let lsusb = lsusb_options(cli, config);
+    let scope = IdScope::new(cli);
+    let interval = Duration::from_millis(cli.interval_ms);
+    let mut stdout = std::io::stdout().lock();
+
+    let mut devices = get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb).devices;
+    devices.retain(|d| on_bus(d, cli.bus) && scope.contains(d));
+    let export: Vec<DeviceExport> = devices.iter().map(DeviceExport::from).collect();
+    let snapshot = serde_json::json!({
+        "event": "snapshot",
+        "schema_version": SCHEMA_VERSION,
+        "timestamp": Local::now().to_rfc3339(),
+        "devices": export,
+    });
+    if writeln!(stdout, "{}", snapshot)
+        .and_then(|_| stdout.flush())
+        .is_err()
+    {
+        return Ok(()); // Reader went away
+    }
+
+    loop {
+        thread::sleep(interval);
+        let mut new_devices = get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb).devices;
+        new_devices.retain(|d| on_bus(d, cli.bus) && scope.contains(d));
+
+        let old_keys: HashSet<String> = devices.iter().map(|d| d.key()).collect();
+        let new_keys: HashSet<String> = new_devices.iter().map(|d| d.key()).collect();
+        let timestamp = Local::now().to_rfc3339();
+
+        let gone = devices
+            .iter()
+            .filter(|d| !new_keys.contains(&d.key()))
+            .map(|d| ("disconnect", d));
+        let arrived = new_devices
+            .iter()
+            .filter(|d| !old_keys.contains(&d.key()))
+            .map(|d| ("connect", d));
+
+        for (event, device) in gone.chain(arrived) {
+            trace_transition(event == "connect", device);
+            let line = serde_json::json!({
+                "event": event,
+                "timestamp": timestamp,
+                "device": DeviceExport::from(device),
+            });
+            if writeln!(stdout, "{}", line)
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+
+        devices = new_devices;
+    }
+}
+
+/// Headless mode: append connect/disconnect/flapping events to a file until
+/// killed, starting with the inventory at launch so the log stands alone
+fn log_to_file(cli: &Cli, config: &Config, path: &Path) -> Result<()> {
+    let backend = backend(cli);
+    let usb_ids = load_usb_ids();
+    let lsusb = lsusb_options(cli, config);
+    let scope = IdScope::new(cli);
+    let interval = Duration::from_millis(cli.interval_ms);
+    let window = Duration::from_secs(config.flap_window_secs);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("couldn't open {}", path.display()))?;
+    let mut write = |line: String| {
+        file.write_all(line.as_bytes())
+            .wrap_err_with(|| format!("couldn't write to {}", path.display()))
+    };
+
+    let poll = get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb);
+    let mut devices = poll.devices;
+    devices.retain(|d| on_bus(d, cli.bus) && scope.contains(d));
+    let timestamp = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+    write(format!(
+        "{} {:<10} {} devices via {}, polling every {}ms\n",
+        timestamp,
+        "start",
+        devices.len(),
+        backend.name(),
+        cli.interval_ms
+    ))?;
+    for device in &devices {
+        write(event_line(&timestamp, "present", device))?;
+    }
+    for warning in &poll.warnings {
+        write(format!("{} {:<10} {}\n", timestamp, "warning", warning))?;
+    }
+
+    let mut transitions: HashMap<String, VecDeque<Instant>> = HashMap::new();
+    let mut flapping: HashSet<String> = HashSet::new();
+    loop {
+        thread::sleep(interval);
+        let poll = get_usb_devices(backend, &usb_ids, &config.dfu, &lsusb);
+        let mut new_devices = poll.devices;
+        new_devices.retain(|d| on_bus(d, cli.bus) && scope.contains(d));
+        let now = Instant::now();
+        record_transitions(&mut transitions, &devices, &new_devices, now, window);
+        let (arrived, gone) = merge_poll(&mut devices, new_devices);
+
+        let timestamp = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+        let events = gone
+            .iter()
+            .map(|d| ("disconnect", d))
+            .chain(arrived.iter().map(|d| ("connect", d)));
+        for (event, device) in events {
+            trace_transition(event == "connect", device);
+            write(event_line(&timestamp, event, device))?;
+            // Once per episode, not on every bounce after the threshold
+            let changes = transitions.get(&device.flap_key()).map_or(0, VecDeque::len);
+            if changes > config.flap_threshold && flapping.insert(device.flap_key()) {
+                write(event_line(&timestamp, "flapping", device))?;
+            }
+        }
+        flapping.retain(|key| {
+            transitions
+                .get(key)
+                .is_some_and(|times| times.len() > config.flap_threshold)
+        });
+        for warning in &poll.warnings {
+            write(format!("{} {:<10} {}\n", timestamp, "warning", warning))?;
+        }
+    }
+}
+
+/// One --log-file line: "TIMESTAMP EVENT VID:PID BUS:DEV NAME", the name
+/// last since it's the only field with spaces
+fn event_line(timestamp: &str, event: &str, device: &UsbDevice) -> String {
+    format!(
+        "{} {:<10} {} {}:{} {}\n",
+        timestamp,
+        event,
+        device.id(),
+        device.bus,
+        device.device,
+        device.name
+    )
+}
+
+/// Merge a poll into `devices` in place: known keys are updated where they
+/// sit, departed ones dropped and new ones appended in poll order. Returns
+/// (arrived, gone), exactly the connects and disconnects.
+fn merge_poll(
+    devices: &mut Vec<UsbDevice>,
+    new_devices: Vec<UsbDevice>,
+) -> (Vec<UsbDevice>, Vec<UsbDevice>) {
+    let order: Vec<String> = new_devices.iter().map(|d| d.key()).collect();
+    let mut incoming: HashMap<String, UsbDevice> =
+        new_devices.into_iter().map(|d| (d.key(), d)).collect();
+    let mut gone = vec![];
+    devices.retain_mut(|device| match incoming.remove(&device.key()) {
+        Some(update) => {
+            *device = update;
+            true
+        }
+        None => {
+            gone.push(device.clone());
+            false
+        }
+    });
+    let arrived: Vec<UsbDevice> = order
+        .iter()
+        .filter_map(|key| incoming.remove(key))
+        .collect();
+    devices.extend(arrived.iter().cloned());
+    (arrived, gone)
+}
+
+/// Note when each flap key appeared or vanished between two polls, keeping
+/// only the times inside `window` for flap detection
+fn record_transitions(
+    transitions: &mut HashMap<String, VecDeque<Instant>>,
+    old: &[UsbDevice],
+    new: &[UsbDevice],
+    now: Instant,
+    window: Duration,
+) {
+    let old: HashSet<String> = old.iter().map(|d| d.flap_key()).collect();
+    let new: HashSet<String> = new.iter().map(|d| d.flap_key()).collect();
+    for key in old.symmetric_difference(&new) {
+        transitions.entry(key.clone()).or_default().push_back(now);
+    }
+    transitions.retain(|_, times| {
+        while times
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > window)
+        {
+            times.pop_front();
+        }
+        !times.is_empty()
+    });
+}
+
+/// How long a digit prefix or a lone 'g' waits for the rest of the sequence
+const PREFIX_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// Vim-style keys typed ahead of a motion: a count like the 5 in `5j`, or the
+/// first half of `gg`. Digits no motion claims select that bus, as before.
+#[derive(Debug, Default)]
+struct KeyPrefix {
+    count: Option<u32>,
+    g: bool,
+    since: Option<Instant>,
+}
+
+impl KeyPrefix {
+    fn push_digit(&mut self, digit: u32) {
+        let count = self
+            .count
+            .unwrap_or(0)
+            .saturating_mul(10)
+            .saturating_add(digit);
+        self.count = Some(count);
+        self.since = Some(Instant::now());
+    }
+
+    fn push_g(&mut self) {
+        self.g = true;
+        self.since = Some(Instant::now());
+    }
+
+    fn expired(&self) -> bool {
+        self.since
+            .is_some_and(|since| since.elapsed() >= PREFIX_TIMEOUT)
+    }
+
+    /// Clear the prefix, handing back any count it held
+    fn take(&mut self) -> Option<u32> {
+        std::mem::take(self).count
+    }
+
+    /// Clear the prefix; a bare count (no pending 'g') means "show only this bus"
+    fn flush(&mut self, app: &mut App) {
+        let g = self.g;
+        if let (Some(bus), false) = (self.take(), g) {
+            app.toggle_bus(bus);
+        }
+    }
+}
+
+fn run(
+    mut terminal: DefaultTerminal,
+    cli: &Cli,
+    config: Config,
+    config_warning: Option<String>,
+) -> Result<()> {
+    let mut app = App::new(cli, config);
+    if let Some(warning) = config_warning {
+        app.set_status(warning, app.theme.warn);
+    }
+
+    let mut prefix = KeyPrefix::default();
+
+    loop {
+        if prefix.expired() {
+            prefix.flush(&mut app);
+        }
+
+        // Check for new device data (non-blocking)
+        app.try_receive_devices();
+        app.try_receive_kernel_log();
+        app.try_receive_descriptors();
+        app.try_receive_dfu_descriptor();
+        app.fetch_dfu_descriptor();
+        app.try_receive_eject();
+        app.try_receive_hub_switching();
+        app.fetch_hub_switching();
+
+        terminal.draw(|frame| ui(frame, &mut app))?;
 
         // Poll for events with short timeout for responsive UI
         if event::poll(Duration::from_millis(16))? {
             // ~60fps UI
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
+            match event::read()? {
+                // Anything but 'y' backs out of a pending action
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.pending.is_some() => {
+                    let action = app.pending.take().expect("checked by the guard");
+                    if key.code == KeyCode::Char('y') {
+                        perform_action(&mut terminal, &mut app, action)?;
+                    } else {
+                        app.set_status("Cancelled".to_string(), app.theme.muted);
+                    }
+                }
+                // Any of the help keys, Esc or q closes the overlay; nothing else goes through
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.show_help => {
+                    let action = app.keymap.action(key.code);
+                    if matches!(key.code, KeyCode::Esc | KeyCode::Char('q'))
+                        || matches!(action, Some(Action::Help | Action::Back | Action::Quit))
+                    {
+                        app.show_help = false;
+                    }
+                }
+                Event::Key(key)
+                    if key.kind == KeyEventKind::Press && app.descriptor_view.is_some() =>
+                {
+                    let action = app.keymap.action(key.code);
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('i') => {
+                            app.close_descriptors()
+                        }
+                        _ if matches!(
+                            action,
+                            Some(Action::Back | Action::Quit | Action::Descriptors)
+                        ) =>
+                        {
+                            app.close_descriptors()
+                        }
+                        KeyCode::Down => app.scroll_descriptors(1),
+                        KeyCode::Up => app.scroll_descriptors(-1),
+                        _ if action == Some(Action::Next) => app.scroll_descriptors(1),
+                        _ if action == Some(Action::Prev) => app.scroll_descriptors(-1),
+                        KeyCode::PageDown | KeyCode::Char(' ') => app.scroll_descriptors(20),
+                        KeyCode::PageUp => app.scroll_descriptors(-20),
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.show_diff => {
+                    match app.keymap.action(key.code) {
+                        Some(Action::Back | Action::Quit | Action::Diff) => app.show_diff = false,
+                        Some(Action::Baseline) => app.take_baseline(),
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.note_editor.is_some() => {
+                    match key.code {
+                        KeyCode::Esc => app.note_editor = None,
+                        KeyCode::Enter => app.save_note(),
+                        KeyCode::Backspace => {
+                            if let Some(editor) = &mut app.note_editor {
+                                editor.text.pop();
+                            }
+                        }
+                        KeyCode::Char(c) => {
+                            if let Some(editor) = &mut app.note_editor {
+                                editor.text.push(c);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.searching => {
                     match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
-                        KeyCode::Char('r') => app.manual_refresh(),
-                        KeyCode::Down | KeyCode::Char('j') => app.next(),
-                        KeyCode::Up | KeyCode::Char('k') => app.previous(),
+                        KeyCode::Esc => app.clear_search(),
+                        KeyCode::Enter => app.finish_search(),
+                        KeyCode::Backspace => app.pop_search_char(),
+                        KeyCode::Down => app.next(),
+                        KeyCode::Up => app.previous(),
+                        KeyCode::Char(c) => app.push_search_char(c),
                         _ => {}
                     }
                 }
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    match (app.keymap.action(key.code), key.code) {
+                        (None, KeyCode::Char(digit @ '0'..='9')) => {
+                            prefix.push_digit(digit.to_digit(10).unwrap_or(0))
+                        }
+                        (None, KeyCode::Char('g')) if prefix.g => match prefix.take() {
+                            Some(row) => app.goto_row(row),
+                            None => app.dispatch(Action::First),
+                        },
+                        (None, KeyCode::Char('g')) => prefix.push_g(),
+                        (Some(motion @ (Action::Next | Action::Prev)), _)
+                            if prefix.count.is_some() && !prefix.g =>
+                        {
+                            let rows = prefix.take().unwrap_or(1).max(1) as isize;
+                            app.move_by(if motion == Action::Next { rows } else { -rows });
+                        }
+                        (Some(Action::Last), _) if prefix.count.is_some() && !prefix.g => {
+                            let row = prefix.take().unwrap_or(1);
+                            app.goto_row(row);
+                        }
+                        (action, _) => {
+                            prefix.flush(&mut app);
+                            if let Some(action) = action {
+                                app.dispatch(action);
+                            }
+                        }
+                    }
+                }
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                _ => {}
             }
         }
 
-        if app.should_quit {
+        if app.should_quit {
+            break;
+        }
+    }
+
+    // Wait out any scan in progress so no lsusb is left behind on exit
+    app.poller.stop();
+    app.save_stats()
+}
+
+/// Ask the terminal to set the clipboard via the OSC 52 escape sequence
+fn copy_osc52(text: &str) -> std::io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
+/// Describe a failed device action, with a hint for the common permissions case
+fn describe_io_error(err: &std::io::Error) -> String {
+    match err.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            "permission denied (run as root or add a udev rule)".to_string()
+        }
+        _ => err.to_string(),
+    }
+}
+
+/// Distinct drivers across `interfaces`, "cdc_acm" or "cdc_acm, usbhid"
+fn driver_list(interfaces: &[BoundInterface]) -> String {
+    let mut drivers: Vec<&str> = vec![];
+    for bound in interfaces {
+        if !drivers.contains(&bound.driver.as_str()) {
+            drivers.push(&bound.driver);
+        }
+    }
+    drivers.join(", ")
+}
+
+/// Run a confirmed action. 'f', 'R', 'U' and 'A' only queue one; this is where it happens.
+fn perform_action(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    action: PendingAction,
+) -> Result<()> {
+    match action {
+        PendingAction::Flash { devices, skipped } => {
+            flash_devices(terminal, app, &devices, skipped)
+        }
+        PendingAction::Reset(devices) => {
+            // The ioctl blocks while the device re-enumerates
+            let what = match devices.as_slice() {
+                [device] => device.name.clone(),
+                _ => format!("{} devices", devices.len()),
+            };
+            show_status_now(terminal, app, format!("Resetting {}…", what))?;
+            app.reset_devices(&devices);
+            Ok(())
+        }
+        PendingAction::Unbind { device, interfaces } => {
+            app.unbind_drivers(&device, &interfaces);
+            Ok(())
+        }
+        PendingAction::Deauthorize(device) => {
+            app.apply_authorized(&device, false);
+            Ok(())
+        }
+    }
+}
+
+/// Run dfu-util against confirmed DFU devices and report how it went
+fn flash_devices(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    dfu: &[UsbDevice],
+    skipped: usize,
+) -> Result<()> {
+    // Drawn before the screen is handed over, so the switch doesn't look like a hang
+    show_status_now(terminal, app, "Starting dfu-util…".to_string())?;
+    let (results, quit) = run_dfu_util(terminal, dfu)?;
+    if quit != QuitRequest::None {
+        app.should_quit = true;
+        return Ok(());
+    }
+    let failed: Vec<String> = results
+        .iter()
+        .filter_map(|(device, result)| match result {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("{} ({})", device.name, status)),
+            Err(err) => Some(format!("{} ({})", device.name, err)),
+        })
+        .collect();
+    let skipped = if skipped == 0 {
+        String::new()
+    } else {
+        format!(", skipped {} not in DFU mode", skipped)
+    };
+
+    if failed.is_empty() {
+        let summary = match results.len() {
+            1 => "dfu-util finished".to_string(),
+            n => format!("dfu-util finished on {} devices", n),
+        };
+        app.set_status(summary + &skipped, app.theme.good);
+    } else {
+        app.set_status(
+            format!("dfu-util failed: {}{}", failed.join(", "), skipped),
+            app.theme.bad,
+        );
+    }
+    Ok(())
+}
+
+/// Put a status in the footer and draw it straight away, ahead of work that
+/// blocks the loop
+fn show_status_now(terminal: &mut DefaultTerminal, app: &mut App, text: String) -> Result<()> {
+    app.set_status(text, app.theme.accent);
+    terminal.draw(|frame| ui(frame, app))?;
+    Ok(())
+}
+
+/// dfu-util's exit status for each device it was run against
+type FlashResults<'a> = Vec<(&'a UsbDevice, std::io::Result<ExitStatus>)>;
+
+/// What typing "q" during a flash asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuitRequest {
+    None,
+    /// "q": exit as soon as dfu-util is done
+    AfterOperation,
+    /// A second "q": kill dfu-util and exit now
+    Force,
+}
+
+/// Suspend the TUI, run `dfu-util -d VID:PID`, pinned by serial or port, for
+/// each device in turn in the foreground, then bring the TUI back unless the
+/// user asked to quit
+fn run_dfu_util<'a>(
+    terminal: &mut DefaultTerminal,
+    devices: &'a [UsbDevice],
+) -> Result<(FlashResults<'a>, QuitRequest)> {
+    restore_terminal();
+    println!("cursed-usb is paused while dfu-util runs (q + Enter to quit afterwards, twice to kill it)\n");
+
+    let mut quit = QuitRequest::None;
+    let mut results = vec![];
+    for device in devices {
+        if quit == QuitRequest::Force {
+            break;
+        }
+        // -d alone would hit the first match every time when the batch holds
+        // several boards with the same ID. lsusb gives no port path to pin by.
+        let pin: Vec<String> = match &device.serial {
+            Some(serial) => vec!["-S".into(), serial.clone()],
+            None if !device.port_path.is_empty() => vec!["-p".into(), device.port_path_string()],
+            None => vec![],
+        };
+        let mut args = vec!["-d".to_string(), device.id()];
+        args.extend(pin);
+        println!(
+            "$ dfu-util {}    # {} on {}",
+            args.join(" "),
+            device.name,
+            device.key()
+        );
+        let status = Command::new("dfu-util")
+            .args(&args)
+            .stdin(Stdio::null())
+            .spawn()
+            .and_then(|child| wait_for_child(child, &mut quit));
+        if let Err(ref err) = status {
+            println!("couldn't run dfu-util: {}", err);
+        }
+        results.push((device, status));
+    }
+    // Leave the output on screen until the user has read it; quitting leaves
+    // it on the normal screen anyway
+    if quit == QuitRequest::None {
+        println!("\nPress Enter to return to cursed-usb");
+        let mut line = String::new();
+        let _ = std::io::stdin().read_line(&mut line);
+        println!("Returning to cursed-usb…");
+    }
+
+    *terminal = init_terminal()?;
+    terminal.clear()?;
+    Ok((results, quit))
+}
+
+/// Wait for a foreground child, watching stdin (line-buffered while the TUI
+/// is suspended, so every answer needs Enter) for "q": that asks to quit once
+/// it's done, and only a second "q" kills the child. Tearing down
+/// mid-flash is how a bootloader gets bricked.
+fn wait_for_child(mut child: Child, quit: &mut QuitRequest) -> std::io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if !stdin_line_ready(Duration::from_millis(50)) {
+            continue;
+        }
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            // stdin closed: nothing more to watch, just wait it out
+            return child.wait();
+        }
+        if line.trim() != "q" {
+            continue;
+        }
+        if *quit == QuitRequest::None {
+            *quit = QuitRequest::AfterOperation;
+            println!(
+                "Operation in progress — quitting once it exits (q + Enter again to kill it now)"
+            );
+        } else {
+            *quit = QuitRequest::Force;
+            child.kill()?;
+            return child.wait();
+        }
+    }
+}
+
+/// Whether a whole line is waiting on stdin, giving up after `timeout`
+fn stdin_line_ready(timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: one valid pollfd, borrowed only for the duration of the call
+    let ready = unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) };
+    ready > 0 && fd.revents & libc::POLLIN != 0
+}
+
+fn ui(frame: &mut Frame, app: &mut App) {
+    let area = frame.area();
+    let theme = app.theme;
+    app.frame_count += 1;
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        app.list_panes.clear(); // Nothing to click on
+        render_too_small(frame, area, &theme);
+        return;
+    }
+
+    // Main layout: header, content, event log, warning line, footer
+    let log_height = if app.show_log || app.show_kernel_log || app.show_vendors {
+        10
+    } else {
+        0
+    };
+    let warning_height = app.stats.last_warning.is_some() as u16;
+    let main_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),              // Header
+            Constraint::Min(5),                 // Content
+            Constraint::Length(log_height),     // Event log
+            Constraint::Length(warning_height), // Enumeration warning
+            Constraint::Length(3),              // Footer
+        ])
+        .split(area);
+
+    // Header
+    render_header(frame, main_layout[0], app, &theme);
+
+    // Content: device list on left, details on right, unless the list has it all
+    if app.config.layout == ViewLayout::Split {
+        let content_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(app.split_pct),       // Device list
+                Constraint::Percentage(100 - app.split_pct), // Details panel
+            ])
+            .split(main_layout[1]);
+
+        render_device_list(frame, content_layout[0], app, &theme);
+        render_details(frame, content_layout[1], app, &theme);
+    } else {
+        render_device_list(frame, main_layout[1], app, &theme);
+    }
+
+    // The vendor roll-up is a narrow column beside the logs, or the whole strip alone
+    let mut bottom = main_layout[2];
+    if app.show_vendors {
+        let vendor_area = if app.show_log || app.show_kernel_log {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(36)])
+                .split(bottom);
+            bottom = columns[0];
+            columns[1]
+        } else {
+            bottom
+        };
+        render_vendors(frame, vendor_area, app, &theme);
+    }
+    match (app.show_log, app.show_kernel_log) {
+        (true, true) => {
+            let halves = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .split(bottom);
+            render_log(frame, halves[0], app, &theme);
+            render_kernel_log(frame, halves[1], app, &theme);
+        }
+        (true, false) => render_log(frame, bottom, app, &theme),
+        (false, true) => render_kernel_log(frame, bottom, app, &theme),
+        (false, false) => {}
+    }
+
+    if let Some(warning) = &app.stats.last_warning {
+        let line = Line::from(vec![
+            Span::styled(format!(" ⚠ {}", warning), Style::default().fg(theme.warn)),
+            Span::styled(
+                format!("  ({} warnings so far)", app.stats.warning_count),
+                Style::default().fg(theme.muted),
+            ),
+        ]);
+        frame.render_widget(Paragraph::new(line), main_layout[3]);
+    }
+
+    // Footer
+    render_footer(frame, main_layout[4], app, &theme);
+
+    if app.note_editor.is_some() {
+        render_note_editor(frame, area, app, &theme);
+    }
+    if app.descriptor_view.is_some() {
+        render_descriptors(frame, area, app, &theme);
+    }
+    if app.show_diff {
+        render_diff(frame, area, app, &theme);
+    }
+    if app.show_help {
+        render_help(frame, area, app, &theme);
+    }
+    if app.pending.is_some() {
+        render_confirm(frame, area, app, &theme);
+    }
+}
+
+/// Full-screen list of every binding, built from the keymap so remapped
+/// keys show up as they are; column-wrapped when it outgrows the screen
+fn render_help(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let mut rows: Vec<(String, String)> = Action::ALL
+        .into_iter()
+        .map(|action| {
+            let keys = app.keymap.labels(action);
+            let keys = if keys.is_empty() {
+                "-".to_string()
+            } else {
+                keys.join(" ")
+            };
+            (keys, action.hint().to_string())
+        })
+        .collect();
+    // Handled outside the keymap, by the count prefix
+    rows.push(("1-9".into(), "bus only / count".into()));
+    rows.push(("g g".into(), "first / row N".into()));
+
+    let key_width = rows
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0);
+    let row_width = rows
+        .iter()
+        .map(|(_, hint)| key_width + 2 + hint.chars().count())
+        .max()
+        .unwrap_or(0) as u16
+        + 4;
+
+    let block = Block::default()
+        .title(" Keys ")
+        .title_bottom(" ? or esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let per_column = inner.height.max(1) as usize;
+    for (index, chunk) in rows.chunks(per_column).enumerate() {
+        let x = inner.x + index as u16 * row_width;
+        if x >= inner.right() {
             break;
         }
+        let column = Rect {
+            x,
+            width: row_width.min(inner.right() - x),
+            ..inner
+        };
+        let lines: Vec<Line> = chunk
+            .iter()
+            .map(|(keys, hint)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!(" {:<width$}  ", keys, width = key_width),
+                        Style::default().fg(theme.accent),
+                    ),
+                    Span::raw(hint.as_str()),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), column);
     }
+}
 
-    Ok(())
+/// Smallest terminal the full layout fits in
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 15;
+
+/// Stand-in for the whole UI until the terminal is resized larger
+fn render_too_small(frame: &mut Frame, area: Rect, theme: &Theme) {
+    let message = Paragraph::new(vec![
+        Line::from(Span::styled(
+            format!("Terminal too small (min {}x{})", MIN_WIDTH, MIN_HEIGHT),
+            Style::default().fg(theme.warn).bold(),
+        )),
+        Line::from(Span::styled(
+            format!("currently {}x{}", area.width, area.height),
+            Style::default().fg(theme.muted),
+        )),
+    ])
+    .alignment(Alignment::Center)
+    .wrap(Wrap { trim: true });
+    let height = 4.min(area.height); // Room for the first line to wrap
+    let centered = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(message, centered);
 }
 
-fn ui(frame: &mut Frame, app: &mut App) {
-    let area = frame.area();
+/// Text box for the selected device's note, with a warning when it will be shared
+fn render_note_editor(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let Some(editor) = &app.note_editor else {
+        return;
+    };
+    let mut lines = vec![
+        Line::from(Span::styled(&editor.name, Style::default().bold())),
+        Line::from(vec![
+            Span::styled("  Key  ", Style::default().fg(theme.muted)),
+            Span::styled(&editor.identity, Style::default().fg(theme.accent)),
+        ]),
+    ];
+    if editor.shared {
+        lines.push(Line::from(Span::styled(
+            "  No serial: every device with this ID shares the note",
+            Style::default().fg(theme.warn),
+        )));
+    }
+    lines.extend([
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(theme.accent)),
+            Span::raw(&editor.text),
+            Span::styled("█", Style::default().fg(theme.accent)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("enter", Style::default().fg(theme.accent)),
+            Span::raw(" save (empty removes)  "),
+            Span::styled("esc", Style::default().fg(theme.accent)),
+            Span::raw(" cancel"),
+        ]),
+    ]);
 
-    // Main layout: header, content, footer
-    let main_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3), // Header
-            Constraint::Min(5),    // Content
-            Constraint::Length(3), // Footer
-        ])
-        .split(area);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let width = 60.min(area.width);
+    let modal = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let overlay = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(" Note ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent)),
+    );
+    frame.render_widget(Clear, modal);
+    frame.render_widget(overlay, modal);
+}
 
-    // Header
-    render_header(frame, main_layout[0], app);
+/// Modal listing exactly what a pending action will hit
+fn render_confirm(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let Some(action) = &app.pending else {
+        return;
+    };
+    let (title, verb) = match action {
+        PendingAction::Flash { .. } => (" Run dfu-util? ", "flash"),
+        PendingAction::Reset(_) => (" Reset device? ", "reset"),
+        PendingAction::Unbind { .. } => (" Unbind driver? ", "unbind"),
+        PendingAction::Deauthorize(_) => (" Deauthorize device? ", "deauthorize"),
+    };
 
-    // Content: device list on left, details on right
-    let content_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(55), // Device list
-            Constraint::Percentage(45), // Details panel
-        ])
-        .split(main_layout[1]);
+    let mut lines = vec![];
+    for device in action.devices() {
+        lines.push(Line::from(Span::styled(
+            &device.name,
+            Style::default().bold(),
+        )));
+        lines.push(Line::from(vec![
+            Span::styled("  ID      ", Style::default().fg(theme.muted)),
+            Span::styled(device.id(), Style::default().fg(theme.accent)),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled("  Serial  ", Style::default().fg(theme.muted)),
+            Span::raw(device.serial.as_deref().unwrap_or("—")),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled("  Path    ", Style::default().fg(theme.muted)),
+            Span::raw(device.display_path()),
+        ]));
+    }
+    if let PendingAction::Unbind { interfaces, .. } = action {
+        for bound in interfaces {
+            lines.push(Line::from(vec![
+                Span::styled("  Driver  ", Style::default().fg(theme.muted)),
+                Span::styled(&bound.driver, Style::default().fg(theme.bad).bold()),
+                Span::styled(
+                    format!(" on {}", bound.interface),
+                    Style::default().fg(theme.muted),
+                ),
+            ]));
+        }
+    }
+    if let PendingAction::Flash { skipped, .. } = action {
+        if *skipped > 0 {
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "{} selected device(s) not in DFU mode will be skipped",
+                    skipped
+                ),
+                Style::default().fg(theme.warn),
+            )));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("y", Style::default().fg(theme.bad).bold()),
+        Span::raw(format!(" {}  ", verb)),
+        Span::styled("any other key", Style::default().fg(theme.accent)),
+        Span::raw(" cancel"),
+    ]));
 
-    render_device_list(frame, content_layout[0], app);
-    render_details(frame, content_layout[1], app);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let width = 60.min(area.width);
+    let modal = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let overlay = Paragraph::new(lines).block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.bad)),
+    );
+    frame.render_widget(Clear, modal);
+    frame.render_widget(overlay, modal);
+}
 
-    // Footer
-    render_footer(frame, main_layout[2], app);
+fn render_descriptors(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let Some(view) = &app.descriptor_view else {
+        return;
+    };
+    let mut lines: Vec<Line> = app
+        .descriptor_cache
+        .get(&view.key)
+        .into_iter()
+        .flatten()
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    let total = lines.len();
+    if app.descriptor_fetch.is_some() && total == 0 {
+        lines.push(Line::styled(
+            format!("{} Running lsusb -v…", spinner_frame(app)),
+            Style::default().fg(theme.muted),
+        ));
+    }
+
+    let overlay = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!(" Descriptors {} ", view.key))
+                .title_bottom(format!(
+                    " {}/{}  j/k scroll  esc close ",
+                    (view.scroll as usize + 1).min(total),
+                    total
+                ))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.accent)),
+        )
+        .scroll((view.scroll, 0));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(overlay, area);
+}
+
+/// Full-screen diff of the live device list against the baseline
+fn render_diff(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let Some(baseline) = &app.baseline else {
+        return;
+    };
+    let diff = SnapshotDiff::new(&baseline.devices, &app.devices);
+
+    let row = |marker: &'static str, color: Color, device: &UsbDevice| {
+        Line::from(vec![
+            Span::styled(marker, Style::default().fg(color).bold()),
+            Span::raw(" "),
+            Span::raw(device.name.clone()),
+            Span::styled(
+                format!("  {}  {}", device.id(), device.key()),
+                Style::default().fg(theme.muted),
+            ),
+        ])
+    };
+    let mut lines = vec![];
+    lines.extend(diff.added.iter().map(|d| row("+", theme.good, d)));
+    lines.extend(diff.removed.iter().map(|d| row("−", theme.bad, d)));
+    for (device, changes) in &diff.changed {
+        lines.push(row("~", theme.warn, device));
+        for change in changes {
+            lines.push(Line::from(Span::styled(
+                format!("    {}", change),
+                Style::default().fg(theme.warn),
+            )));
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No changes since the baseline",
+            Style::default().fg(theme.muted),
+        )));
+    }
+
+    let overlay = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(
+                " Changes since {} · +{} −{} ~{} ",
+                baseline.taken_at.format("%H:%M:%S"),
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len()
+            ))
+            .title_bottom(match app.keymap.label(Action::Baseline) {
+                Some(key) => format!(" {} new baseline  esc close ", key),
+                None => " esc close ".to_string(),
+            })
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(overlay, area);
 }
 
-fn render_header(frame: &mut Frame, area: Rect, app: &App) {
+fn render_header(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
     let dfu_count = app.dfu_count();
     let mut spans = vec![
-        Span::styled("USB Devices ", Style::default().fg(Color::Cyan).bold()),
+        Span::styled("USB Devices ", Style::default().fg(theme.accent).bold()),
         Span::styled(
-            format!("({})", app.devices.len()),
-            Style::default().fg(Color::DarkGray),
+            if !app.is_filtered() {
+                format!("({})", app.shown_count())
+            } else {
+                format!("({} of {})", app.visible_devices().len(), app.shown_count())
+            },
+            Style::default().fg(theme.muted),
         ),
     ];
 
-    if dfu_count > 0 {
+    let hidden = app.devices.len() - app.shown_count();
+    if hidden > 0 {
+        spans.push(Span::styled(
+            format!(" +{} internal", hidden),
+            Style::default().fg(theme.muted),
+        ));
+    }
+
+    if let Some(scope) = app.scope.label() {
         spans.push(Span::raw("  "));
         spans.push(Span::styled(
-            format!(" {} DFU ", dfu_count),
+            format!(" {} only ", scope),
+            Style::default()
+                .fg(theme.badge_text)
+                .bg(theme.accent)
+                .bold(),
+        ));
+    }
+
+    if let Some(bus) = app.bus_filter {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(
+            format!(" bus {} only ", bus),
+            Style::default()
+                .fg(theme.badge_text)
+                .bg(theme.accent)
+                .bold(),
+        ));
+    }
+
+    if app.follow_new {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(
+            " following new ",
             Style::default()
-                .fg(Color::White)
-                .bg(Color::Magenta)
+                .fg(theme.badge_text)
+                .bg(theme.accent)
                 .bold(),
         ));
     }
 
+    if app.dfu_only {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(
+            " DFU-only view ",
+            Style::default().fg(theme.badge_text).bg(theme.warn).bold(),
+        ));
+    }
+
+    if dfu_count > 0 {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(
+            format!(" {} DFU ", dfu_count),
+            Style::default().fg(theme.text).bg(theme.dfu).bold(),
+        ));
+    }
+
+    if app.paused {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(
+            "⏸ PAUSED",
+            Style::default().fg(theme.warn).bold(),
+        ));
+    }
+
     // Add uptime on the right
     spans.push(Span::raw("  "));
     spans.push(Span::styled(
         format!("uptime {}", app.stats.format_uptime()),
-        Style::default().fg(Color::DarkGray),
+        Style::default().fg(theme.muted),
     ));
 
     let header = Paragraph::new(Line::from(spans))
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Blue)),
+                .border_style(Style::default().fg(theme.border)),
         )
         .style(Style::default());
 
     frame.render_widget(header, area);
 }
 
-fn render_device_list(frame: &mut Frame, area: Rect, app: &mut App) {
-    let items: Vec<ListItem> = app
-        .devices
-        .iter()
+/// Stable color for a vendor ID (FNV-1a, so it doesn't change between runs)
+fn vendor_color(vendor_id: &str, theme: &Theme) -> Color {
+    let hash = vendor_id
+        .to_lowercase()
+        .bytes()
+        .fold(0x811c9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+    theme.vendors[hash as usize % theme.vendors.len()]
+}
+
+/// Split a device name into its leading vendor part (if the vendor name is
+/// known and actually starts the name) and the rest
+fn split_vendor_prefix(device: &UsbDevice) -> (&str, &str) {
+    let name = device.name.as_str();
+    let vendor = [&device.vendor_name, &device.manufacturer]
+        .into_iter()
+        .flatten()
+        .find(|vendor| {
+            !vendor.is_empty()
+                && name
+                    .get(..vendor.len())
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(vendor))
+        });
+    match vendor {
+        Some(vendor) => name.split_at(vendor.len()),
+        None => ("", name),
+    }
+}
+
+/// Background for a just-arrived row, dimming in steps until ARRIVAL_HIGHLIGHT
+fn arrival_tint(age: Duration, theme: &Theme) -> Option<Color> {
+    let progress = age.as_secs_f64() / ARRIVAL_HIGHLIGHT.as_secs_f64();
+    let step = (progress * theme.arrival.len() as f64) as usize;
+    theme.arrival.get(step).copied()
+}
+
+/// Shortest a name gets squeezed to before the row is left to clip instead
+const MIN_NAME_WIDTH: usize = 12;
+
+/// Split `name` into a head and tail that fit `width` columns with an
+/// ellipsis between them, keeping more of the head: "STMicro STLINK…V2-1"
+fn middle_truncate(name: &str, width: usize) -> (String, String) {
+    if name.width() <= width {
+        return (name.to_string(), String::new());
+    }
+    let kept = width.saturating_sub(1);
+    let tail_budget = kept * 2 / 5;
+    let head_budget = kept - tail_budget;
+    (
+        take_columns(name.chars(), head_budget),
+        take_columns(name.chars().rev(), tail_budget)
+            .chars()
+            .rev()
+            .collect(),
+    )
+}
+
+/// Collect chars until the next one would overflow `columns` terminal cells
+fn take_columns(chars: impl Iterator<Item = char>, columns: usize) -> String {
+    let mut used = 0;
+    chars
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= columns
+        })
+        .collect()
+}
+
+/// Shorten the name (the vendor and rest spans at `name_at`) from the
+/// middle until the row fits `width`, so the ID and path after it stay visible
+fn fit_name(spans: &mut [Span], name_at: usize, width: usize) {
+    let total: usize = spans.iter().map(Span::width).sum();
+    if total <= width {
+        return;
+    }
+    let vendor_len = spans[name_at].content.chars().count();
+    let name = format!("{}{}", spans[name_at].content, spans[name_at + 1].content);
+    let name_width = name.width();
+    let budget = name_width.saturating_sub(total - width).max(MIN_NAME_WIDTH);
+    if budget >= name_width {
+        return;
+    }
+    let (head, tail) = middle_truncate(&name, budget);
+    let vendor: String = head.chars().take(vendor_len).collect();
+    let rest: String = head.chars().skip(vendor_len).collect();
+    spans[name_at].content = vendor.into();
+    spans[name_at + 1].content = format!("{}…{}", rest, tail).into();
+}
+
+fn render_device_list(frame: &mut Frame, area: Rect, app: &mut App, theme: &Theme) {
+    let layout = app.config.layout;
+    let grouping = app.group_duplicates && !app.tree_view;
+    // Inside the borders and the "▶ " highlight column
+    let pane_width = if layout == ViewLayout::Columns {
+        area.width / 2
+    } else {
+        area.width
+    };
+    let row_width = pane_width.saturating_sub(4) as usize;
+    let mut group_heads = HashSet::new();
+    let mut items: Vec<ListItem> = app
+        .visible_devices()
+        .into_iter()
         .map(|device| {
             let name_style = if device.is_dfu {
-                Style::default().fg(Color::Yellow).bold()
+                Style::default().fg(theme.warn).bold()
             } else {
                 Style::default()
             };
 
             let path = device.display_path();
-            let path_style = if device.tty_path.is_some() {
-                Style::default().fg(Color::Green) // TTY paths in green
+            let path_style = if !device.tty_paths.is_empty() {
+                Style::default().fg(theme.good) // TTY paths in green
             } else {
-                Style::default().fg(Color::DarkGray)
+                Style::default().fg(theme.muted)
             };
 
-            let content = Line::from(vec![
-                Span::styled(&device.name, name_style),
-                Span::raw(" "),
-                Span::styled(path, path_style),
-            ]);
+            let mut spans = vec![];
+            if app.tree_view {
+                let indent = "  ".repeat(device.port_path.len());
+                let fold = if !app.has_children(device) {
+                    "  "
+                } else if app.collapsed.contains(&device.port_path_string()) {
+                    "▸ "
+                } else {
+                    "▾ "
+                };
+                spans.push(Span::styled(
+                    format!("{}{}", indent, fold),
+                    Style::default().fg(theme.muted),
+                ));
+            }
+            // (size, expanded, first of its group) for identical devices when grouping
+            let group = if grouping {
+                let size = app.group_size(device);
+                (size > 1).then(|| {
+                    let expanded = app.expanded_groups.contains(&device.id());
+                    (size, expanded, group_heads.insert(device.id()))
+                })
+            } else {
+                None
+            };
+            match group {
+                Some((size, expanded, true)) => spans.push(Span::styled(
+                    format!("{} {}× ", if expanded { "▾" } else { "▸" }, size),
+                    Style::default().fg(theme.accent).bold(),
+                )),
+                Some((_, _, false)) => {
+                    spans.push(Span::styled("  └ ", Style::default().fg(theme.muted)))
+                }
+                None => {}
+            }
+            if !app.selected_keys.is_empty() {
+                let marker = if app.selected_keys.contains(&device.key()) {
+                    "[x] "
+                } else {
+                    "[ ] "
+                };
+                spans.push(Span::styled(marker, Style::default().fg(theme.accent)));
+            }
+            // DFU highlighting wins over the vendor tint on the name
+            let vendor_style = if app.vendor_colors && !device.is_dfu {
+                name_style.fg(vendor_color(&device.vendor_id, theme))
+            } else {
+                name_style
+            };
+            let (vendor, rest) = split_vendor_prefix(device);
+            let name_at = spans.len();
+            spans.push(Span::styled(vendor.to_string(), vendor_style));
+            spans.push(Span::styled(rest.to_string(), name_style));
+            if let Some(note) = app.notes.get(device) {
+                spans.push(Span::styled(
+                    format!(" [{}]", note),
+                    Style::default().fg(theme.accent).italic(),
+                ));
+            }
+            if layout == ViewLayout::Full {
+                let id_color = if app.vendor_colors {
+                    vendor_color(&device.vendor_id, theme)
+                } else {
+                    theme.accent
+                };
+                spans.push(Span::raw("  "));
+                spans.push(Span::styled(device.id(), Style::default().fg(id_color)));
+            }
+            match group {
+                // A folded group stands for several devices, so no single path
+                Some((_, false, _)) => spans.push(Span::styled(
+                    format!(" ({})", device.id()),
+                    Style::default().fg(theme.muted),
+                )),
+                Some((_, true, _)) => {
+                    let unit = device.serial.as_deref().map_or_else(
+                        || format!(" {}", device.key()),
+                        |serial| format!(" #{}", serial),
+                    );
+                    spans.push(Span::styled(unit, Style::default().fg(theme.accent)));
+                    spans.extend([Span::raw(" "), Span::styled(path.to_string(), path_style)]);
+                }
+                None => spans.extend([Span::raw(" "), Span::styled(path.to_string(), path_style)]),
+            }
+            if let Some(tty) = app.highlighted_tty(device) {
+                spans.push(Span::styled(
+                    format!(" ◀ {}", tty),
+                    Style::default().fg(theme.accent).bold(),
+                ));
+            }
+            if app.is_active(device) {
+                let frame = app.stats.start_time.elapsed().as_millis() / 100;
+                let spinner = ACTIVITY_FRAMES[frame as usize % ACTIVITY_FRAMES.len()];
+                spans.push(Span::styled(
+                    format!(" {}", spinner),
+                    Style::default().fg(theme.good),
+                ));
+            }
+            if device.expects_tty() && device.tty_paths.is_empty() {
+                spans.push(Span::styled(
+                    " no tty — driver?",
+                    Style::default().fg(theme.warn),
+                ));
+            }
+            if app.is_flapping(device) {
+                spans.push(Span::styled(
+                    " ⚠ flapping",
+                    Style::default()
+                        .fg(theme.bad)
+                        .bold()
+                        .add_modifier(Modifier::SLOW_BLINK),
+                ));
+            }
+            fit_name(&mut spans, name_at, row_width);
+            let content = Line::from(spans);
 
-            ListItem::new(content)
+            match app
+                .arrived_at
+                .get(&device.key())
+                .and_then(|at| arrival_tint(at.elapsed(), theme))
+            {
+                Some(tint) => ListItem::new(content).style(Style::default().bg(tint)),
+                None => ListItem::new(content),
+            }
         })
         .collect();
 
-    let list = List::new(items)
+    let order = if app.tree_view {
+        "tree".to_string()
+    } else if grouping {
+        format!("by {} · grouped", app.sort_mode.label())
+    } else {
+        format!("by {}", app.sort_mode.label())
+    };
+    let order = match app.tty_filter.label() {
+        Some(mode) => format!("{} · {}", order, mode),
+        None => order,
+    };
+    let title = if app.selected_keys.is_empty() {
+        format!(" Devices · {} ", order)
+    } else {
+        format!(
+            " Devices · {} · {} selected ",
+            order,
+            app.selected_keys.len()
+        )
+    };
+    // Empty because nothing is plugged in, or because a filter hides it all
+    let placeholder = if !items.is_empty() {
+        None
+    } else if app.dfu_only && !app.devices.iter().any(|d| d.is_dfu) {
+        Some("Waiting for DFU device…")
+    } else if app.is_filtered() && !app.devices.is_empty() {
+        Some("No matches")
+    } else {
+        None
+    };
+    items.extend(departed_items(app, theme));
+
+    if layout == ViewLayout::Columns {
+        render_list_columns(frame, area, app, items, title, theme);
+    } else {
+        let count = items.len();
+        if let Some(selected) = app.list_state.selected() {
+            let rows = area.height.saturating_sub(2) as usize;
+            *app.list_state.offset_mut() =
+                scroll_offset(app.list_state.offset(), selected, rows, count);
+        }
+        let list = device_list_widget(items, title, theme);
+        frame.render_stateful_widget(list, area, &mut app.list_state);
+        app.list_panes = vec![(area, app.list_state.offset())];
+
+        if count > area.height.saturating_sub(2) as usize {
+            let mut scroll =
+                ScrollbarState::new(count).position(app.list_state.selected().unwrap_or(0));
+            frame.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                    .begin_symbol(None)
+                    .end_symbol(None),
+                area.inner(Margin::new(0, 1)),
+                &mut scroll,
+            );
+        }
+    }
+
+    if let Some(placeholder) = placeholder {
+        let inner = area.inner(Margin::new(1, 1));
+        let placeholder_area = Rect {
+            y: inner.y + inner.height.saturating_sub(1) / 2,
+            height: 1.min(inner.height),
+            ..inner
+        };
+        let placeholder = Paragraph::new(placeholder)
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.warn));
+        frame.render_widget(placeholder, placeholder_area);
+    }
+}
+
+/// Rows kept in view above and below the selection, like vim's scrolloff
+const SCROLL_OFF: usize = 2;
+
+/// First list row to draw: the current one, moved as little as it takes to
+/// keep SCROLL_OFF rows around `selected` (fewer when the list is too short)
+fn scroll_offset(offset: usize, selected: usize, rows: usize, count: usize) -> usize {
+    if rows == 0 || count <= rows {
+        return 0;
+    }
+    let margin = SCROLL_OFF.min((rows - 1) / 2);
+    let lowest = (selected + margin + 1).saturating_sub(rows);
+    let highest = selected.saturating_sub(margin);
+    offset.clamp(lowest, highest).min(count - rows)
+}
+
+fn device_list_widget<'a>(items: Vec<ListItem<'a>>, title: String, theme: &Theme) -> List<'a> {
+    List::new(items)
         .block(
             Block::default()
-                .title(" Devices ")
+                .title(title)
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Blue)),
+                .border_style(Style::default().fg(theme.border)),
         )
         .highlight_style(
             Style::default()
-                .bg(Color::DarkGray)
+                .bg(theme.selection)
                 .add_modifier(Modifier::BOLD),
         )
-        .highlight_symbol("▶ ");
+        .highlight_symbol("▶ ")
+        .highlight_spacing(HighlightSpacing::Always)
+}
+
+/// Grayed-out rows for devices that left this session, newest first. They
+/// sit below the live rows, so list indices still match `visible_devices`.
+fn departed_items(app: &App, theme: &Theme) -> Vec<ListItem<'static>> {
+    if !app.show_departed {
+        return vec![];
+    }
+    let mut departed: Vec<&(UsbDevice, Instant)> = app
+        .departed
+        .values()
+        .filter(|(device, _)| app.is_listed(device))
+        .collect();
+    if departed.is_empty() {
+        return vec![];
+    }
+    departed.sort_by_key(|(_, left)| std::cmp::Reverse(*left));
+
+    let style = Style::default().fg(theme.muted);
+    let mut items = vec![ListItem::new(Line::styled(
+        format!("── departed ({}) ──", departed.len()),
+        style,
+    ))];
+    items.extend(departed.into_iter().map(|(device, left)| {
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("  {} ", device.name), style),
+            Span::styled(device.display_path().to_string(), style),
+            Span::styled(
+                format!("  gone {} ago", format_hms(left.elapsed())),
+                style.italic(),
+            ),
+        ]))
+    }));
+    items
+}
+
+/// Flow the list down the left column then the right, a page at a time
+fn render_list_columns(
+    frame: &mut Frame,
+    area: Rect,
+    app: &mut App,
+    mut items: Vec<ListItem>,
+    title: String,
+    theme: &Theme,
+) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    let rows = (area.height.saturating_sub(2) as usize).max(1);
+    let selected = app.list_state.selected();
+    let page_start = selected.map_or(0, |index| index / (rows * 2) * rows * 2);
 
-    frame.render_stateful_widget(list, area, &mut app.list_state);
+    items.drain(..page_start.min(items.len()));
+    let right_items = items.split_off(rows.min(items.len()));
+    app.list_panes.clear();
+    for (column, (column_items, first)) in [(items, page_start), (right_items, page_start + rows)]
+        .into_iter()
+        .enumerate()
+    {
+        let column_title = if column == 0 {
+            title.clone()
+        } else {
+            String::new()
+        };
+        let mut state = ListState::default().with_selected(
+            selected
+                .and_then(|index| index.checked_sub(first))
+                .filter(|&i| i < rows),
+        );
+        let list = device_list_widget(column_items, column_title, theme);
+        frame.render_stateful_widget(list, columns[column], &mut state);
+        app.list_panes.push((columns[column], first));
+    }
 }
 
-fn render_details(frame: &mut Frame, area: Rect, app: &App) {
+fn render_details(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
     let block = Block::default()
         .title(" Details ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Blue));
+        .border_style(Style::default().fg(theme.border));
 
     let inner = block.inner(area);
     frame.render_widget(block, area);
@@ -562,7 +5418,7 @@ fn render_details(frame: &mut Frame, area: Rect, app: &App) {
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Min(8),     // Device details
-            Constraint::Length(10), // Stats
+            Constraint::Length(18), // Stats: 12 lines, then the sparkline's title and 5 rows
         ])
         .split(inner);
 
@@ -570,42 +5426,223 @@ fn render_details(frame: &mut Frame, area: Rect, app: &App) {
     if let Some(device) = app.selected_device() {
         let mut lines = vec![
             Line::from(vec![
-                Span::styled("Name     ", Style::default().fg(Color::DarkGray)),
-                Span::styled(&device.name, Style::default().bold()),
+                Span::styled("Name     ", Style::default().fg(theme.muted)),
+                Span::styled(&device.name, Style::default().bold()),
+            ]),
+            Line::from(vec![
+                Span::styled("Made by  ", Style::default().fg(theme.muted)),
+                Span::raw(device.vendor_name.as_deref().unwrap_or("unknown vendor")),
+            ]),
+        ];
+        if let Some(note) = app.notes.get(device) {
+            lines.push(Line::from(vec![
+                Span::styled("Note     ", Style::default().fg(theme.muted)),
+                Span::styled(note, Style::default().fg(theme.accent).bold()),
+            ]));
+        }
+        lines.extend([
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("ID       ", Style::default().fg(theme.muted)),
+                Span::styled(device.id(), Style::default().fg(theme.accent)),
+                Span::styled(
+                    format!(" ({})", device.decimal_id()),
+                    Style::default().fg(theme.muted),
+                ),
+            ]),
+            Line::from(vec![
+                Span::styled("Bus      ", Style::default().fg(theme.muted)),
+                Span::raw(&device.bus),
+            ]),
+            Line::from(vec![
+                Span::styled("Device   ", Style::default().fg(theme.muted)),
+                Span::raw(&device.device),
+            ]),
+            Line::from(vec![
+                Span::styled("Vendor   ", Style::default().fg(theme.muted)),
+                Span::raw(&device.vendor_id),
+            ]),
+            Line::from(vec![
+                Span::styled("Product  ", Style::default().fg(theme.muted)),
+                Span::raw(&device.product_id),
             ]),
-            Line::from(""),
             Line::from(vec![
-                Span::styled("ID       ", Style::default().fg(Color::DarkGray)),
-                Span::styled(device.id(), Style::default().fg(Color::Cyan)),
+                Span::styled("FW Rev   ", Style::default().fg(theme.muted)),
+                Span::raw(device.fw_version.as_deref().unwrap_or("—")),
             ]),
             Line::from(vec![
-                Span::styled("Bus      ", Style::default().fg(Color::DarkGray)),
-                Span::raw(&device.bus),
+                Span::styled("USB Spec ", Style::default().fg(theme.muted)),
+                Span::raw(device.usb_version.as_deref().unwrap_or("—")),
+                if device.fell_back_from_usb3() {
+                    Span::styled(
+                        "  running at USB 2 speed — cable or port?",
+                        Style::default().fg(theme.warn),
+                    )
+                } else {
+                    Span::raw("")
+                },
             ]),
             Line::from(vec![
-                Span::styled("Device   ", Style::default().fg(Color::DarkGray)),
-                Span::raw(&device.device),
+                Span::styled("Mfr      ", Style::default().fg(theme.muted)),
+                Span::raw(device.manufacturer.as_deref().unwrap_or("—")),
             ]),
             Line::from(vec![
-                Span::styled("Vendor   ", Style::default().fg(Color::DarkGray)),
-                Span::raw(&device.vendor_id),
+                Span::styled("Serial   ", Style::default().fg(theme.muted)),
+                match &device.serial {
+                    Some(serial) => Span::styled(serial, Style::default().fg(theme.accent)),
+                    None => Span::styled("—", Style::default().fg(theme.muted)),
+                },
             ]),
             Line::from(vec![
-                Span::styled("Product  ", Style::default().fg(Color::DarkGray)),
-                Span::raw(&device.product_id),
+                Span::styled("Class    ", Style::default().fg(theme.muted)),
+                match device.class_code {
+                    Some(code) => Span::raw(format!(
+                        "{} ({:02x}:{:02x})",
+                        class_label(code),
+                        code,
+                        device.subclass_code.unwrap_or(0)
+                    )),
+                    None => Span::styled("unknown", Style::default().fg(theme.muted)),
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Driver   ", Style::default().fg(theme.muted)),
+                if !device.drivers.is_empty() {
+                    Span::raw(device.drivers.join(", "))
+                } else if app.backend == Backend::Lsusb {
+                    Span::styled("unknown", Style::default().fg(theme.muted))
+                } else {
+                    // Nothing bound is usually why the tty never showed up
+                    Span::styled("(none)", Style::default().fg(theme.warn))
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Speed    ", Style::default().fg(theme.muted)),
+                match device.speed {
+                    Some(speed) => Span::styled(
+                        format!("{} Mbps ({})", speed.mbps(), speed.label()),
+                        Style::default().fg(speed_color(speed, theme)),
+                    ),
+                    None => Span::styled("unknown", Style::default().fg(theme.muted)),
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("EP0 MaxPkt ", Style::default().fg(theme.muted)),
+                match device.ep0_max_packet() {
+                    Some(size) => Span::raw(size.to_string()),
+                    None => Span::styled("unknown", Style::default().fg(theme.muted)),
+                },
+                match (device.ep0_max_packet(), device.speed) {
+                    // The spec fixes EP0 at 64 bytes for high speed and 8 for low
+                    (Some(size), Some(UsbSpeed::High)) if size != 64 => Span::styled(
+                        "  high speed should be 64 — negotiated as full speed?",
+                        Style::default().fg(theme.warn),
+                    ),
+                    (Some(size), Some(UsbSpeed::Low)) if size != 8 => {
+                        Span::styled("  low speed should be 8", Style::default().fg(theme.warn))
+                    }
+                    _ => Span::raw(""),
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Endpoints ", Style::default().fg(theme.muted)),
+                if app.backend == Backend::Lsusb {
+                    Span::styled("unknown", Style::default().fg(theme.muted))
+                } else if device.endpoint_count == 0 {
+                    // Enumerated, but nothing beyond ep0: the function never came up
+                    Span::styled("0 (control only)", Style::default().fg(theme.warn))
+                } else {
+                    Span::raw(format!(
+                        "{} {}",
+                        device.endpoint_count,
+                        "▮".repeat(device.endpoint_count.min(16))
+                    ))
+                },
             ]),
+        ]);
+        if !device.endpoint_kinds.is_empty() {
+            lines.push(Line::from(vec![
+                Span::raw("          "),
+                Span::styled(
+                    summarize_endpoints(&device.endpoint_kinds),
+                    Style::default().fg(theme.muted),
+                ),
+            ]));
+        }
+        if let Some(urbs) = device.urb_count {
+            lines.push(Line::from(vec![
+                Span::styled("Activity ", Style::default().fg(theme.muted)),
+                if app.is_active(device) {
+                    Span::styled("active", Style::default().fg(theme.good).bold())
+                } else {
+                    Span::styled("idle", Style::default().fg(theme.muted))
+                },
+                Span::styled(format!("  {} URBs", urbs), Style::default().fg(theme.muted)),
+            ]));
+        }
+        let state = match (device.state_problem(), &device.runtime_status) {
+            (Some(problem), _) => {
+                Some(Span::styled(problem, Style::default().fg(theme.bad).bold()))
+            }
+            (None, Some(status)) => Some(Span::raw(status.clone())),
+            (None, None) => None,
+        };
+        if let Some(state) = state {
+            lines.push(Line::from(vec![
+                Span::styled("State    ", Style::default().fg(theme.muted)),
+                state,
+            ]));
+        }
+        lines.extend(power_lines(device, theme));
+        lines.extend(port_lines(device, app, theme));
+        if !device.diagnostics.is_empty() {
+            lines.push(Line::from(vec![
+                Span::styled("Diagnostics ", Style::default().fg(theme.muted)),
+                Span::styled(
+                    device.diagnostics.join("; "),
+                    Style::default().fg(theme.muted).add_modifier(Modifier::DIM),
+                ),
+            ]));
+        }
+        lines.extend([
             Line::from(""),
             Line::from(vec![
-                Span::styled("Path     ", Style::default().fg(Color::DarkGray)),
-                Span::styled(&device.dev_path, Style::default().fg(Color::Green)),
+                Span::styled("Port path ", Style::default().fg(theme.muted)),
+                if app.backend == Backend::Lsusb {
+                    Span::styled("unknown", Style::default().fg(theme.muted))
+                } else if device.port_path.is_empty() {
+                    Span::raw(format!("{} (root hub)", device.port_path_string()))
+                } else {
+                    Span::raw(device.port_path_string())
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Path     ", Style::default().fg(theme.muted)),
+                Span::styled(&device.dev_path, Style::default().fg(theme.good)),
             ]),
-        ];
+        ]);
+        if let Some(dir) = &device.sysfs_dir {
+            lines.push(Line::from(vec![
+                Span::styled("Sysfs    ", Style::default().fg(theme.muted)),
+                Span::raw(dir.display().to_string()),
+            ]));
+        }
+
+        if let Some(seen) = app.first_seen.get(&device.key()) {
+            lines.push(Line::from(vec![
+                Span::styled("Connected ", Style::default().fg(theme.muted)),
+                Span::raw(format_hms(seen.elapsed())),
+            ]));
+        }
 
-        // Show tty if present
-        if let Some(ref tty) = device.tty_path {
+        // Show ttys if present; composite devices have several
+        if !device.tty_paths.is_empty() {
             lines.push(Line::from(vec![
-                Span::styled("TTY      ", Style::default().fg(Color::DarkGray)),
-                Span::styled(tty, Style::default().fg(Color::Green).bold()),
+                Span::styled("TTY      ", Style::default().fg(theme.muted)),
+                Span::styled(
+                    device.tty_paths.join(", "),
+                    Style::default().fg(theme.good).bold(),
+                ),
             ]));
         }
 
@@ -613,117 +5650,753 @@ fn render_details(frame: &mut Frame, area: Rect, app: &App) {
             lines.push(Line::from(""));
             lines.push(Line::from(Span::styled(
                 "⚡ DFU Mode",
-                Style::default().fg(Color::Yellow).bold(),
+                Style::default().fg(theme.warn).bold(),
             )));
         }
 
         let details = Paragraph::new(lines).wrap(Wrap { trim: true });
         frame.render_widget(details, detail_layout[0]);
     } else {
-        let no_device = Paragraph::new("No device selected")
-            .style(Style::default().fg(Color::DarkGray));
+        let no_device =
+            Paragraph::new("No device selected").style(Style::default().fg(theme.muted));
         frame.render_widget(no_device, detail_layout[0]);
     }
 
-    // Stats section
-    render_stats(frame, detail_layout[1], app);
+    // A bootloader gets its DFU details and actions where the stats would be
+    match app.selected_device().filter(|d| d.is_dfu) {
+        Some(device) => render_dfu_panel(frame, detail_layout[1], app, device, theme),
+        None => render_stats(frame, detail_layout[1], app, theme),
+    }
+}
+
+fn render_dfu_panel(frame: &mut Frame, area: Rect, app: &App, device: &UsbDevice, theme: &Theme) {
+    let mut lines = vec![Line::from(Span::styled(
+        "─── DFU ───",
+        Style::default().fg(theme.muted),
+    ))];
+    match app.dfu_descriptors.get(&device.key()) {
+        Some(Some(dfu)) => {
+            let version = match dfu.version() {
+                // ST's extended protocol, with its own address/erase commands
+                Some(version) if dfu.dfu_version == Some(0x011a) => {
+                    format!("{} (DfuSe)", version)
+                }
+                Some(version) => version,
+                None => "—".to_string(),
+            };
+            lines.extend([
+                Line::from(vec![
+                    Span::styled("Version      ", Style::default().fg(theme.muted)),
+                    Span::raw(version),
+                ]),
+                Line::from(vec![
+                    Span::styled("Attributes   ", Style::default().fg(theme.muted)),
+                    Span::styled(
+                        format!("0x{:02x}  ", dfu.attributes),
+                        Style::default().fg(theme.accent),
+                    ),
+                    Span::raw(dfu.capabilities().join(" · ")),
+                ]),
+                Line::from(vec![
+                    Span::styled("Detach       ", Style::default().fg(theme.muted)),
+                    Span::raw(format!("{} ms", dfu.detach_timeout_ms)),
+                ]),
+                Line::from(vec![
+                    Span::styled("Transfer     ", Style::default().fg(theme.muted)),
+                    Span::raw(format!("{} bytes", dfu.transfer_size)),
+                ]),
+            ]);
+        }
+        Some(None) => lines.push(Line::from(Span::styled(
+            "No DFU functional descriptor found",
+            Style::default().fg(theme.muted),
+        ))),
+        None => lines.push(Line::from(Span::styled(
+            format!("{} Reading DFU descriptor…", spinner_frame(app)),
+            Style::default().fg(theme.muted),
+        ))),
+    }
+
+    lines.push(Line::from(""));
+    let mut prompt = vec![];
+    for (action, verb) in [(Action::Flash, "flash"), (Action::Reset, "reset")] {
+        if let Some(key) = app.keymap.label(action) {
+            prompt.push(Span::styled(
+                format!("[{}]", key),
+                Style::default().fg(theme.accent).bold(),
+            ));
+            prompt.push(Span::raw(format!(" {}  ", verb)));
+        }
+    }
+    lines.push(Line::from(prompt));
+
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+/// Low and Full speed are usually a fallback on anything but HID
+fn speed_color(speed: UsbSpeed, theme: &Theme) -> Color {
+    match speed {
+        UsbSpeed::Low => theme.bad,
+        UsbSpeed::Full => theme.warn,
+        UsbSpeed::High => theme.good,
+        _ => theme.accent,
+    }
+}
+
+/// "2× IN bulk, OUT bulk" keeping first-seen order
+fn summarize_endpoints(kinds: &[String]) -> String {
+    let mut counts: Vec<(&str, usize)> = vec![];
+    for kind in kinds {
+        match counts.iter_mut().find(|(seen, _)| seen == kind) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((kind, 1)),
+        }
+    }
+    counts
+        .iter()
+        .map(|(kind, count)| match count {
+            1 => kind.to_string(),
+            n => format!("{}× {}", n, kind),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Power line for the details panel: a hub's downstream draw against its
+/// budget with a bar, or just the device's own draw
+fn power_lines(device: &UsbDevice, theme: &Theme) -> Vec<Line<'static>> {
+    let label = Span::styled("Power    ", Style::default().fg(theme.muted));
+    let hub = device
+        .sysfs_dir
+        .as_deref()
+        .filter(|_| device.class_code == Some(0x09))
+        .and_then(|dir| hub_power(dir, device.speed));
+
+    let Some(HubPower { draw_ma, budget_ma }) = hub else {
+        let own = match device.max_power_ma {
+            Some(ma) => Span::raw(format!("{} mA", ma)),
+            None => Span::styled("unknown", Style::default().fg(theme.muted)),
+        };
+        return vec![Line::from(vec![label, own])];
+    };
+
+    const BAR_WIDTH: u32 = 20;
+    let ratio = draw_ma as f64 / budget_ma.max(1) as f64;
+    let color = if ratio >= 0.9 {
+        theme.bad
+    } else if ratio >= 0.75 {
+        theme.warn
+    } else {
+        theme.good
+    };
+    let filled = ((ratio * BAR_WIDTH as f64).round() as u32).min(BAR_WIDTH);
+    vec![
+        Line::from(vec![
+            label,
+            Span::styled(
+                format!("{} / {} mA downstream", draw_ma, budget_ma),
+                Style::default().fg(color),
+            ),
+        ]),
+        Line::from(vec![
+            Span::raw("         "),
+            Span::styled("█".repeat(filled as usize), Style::default().fg(color)),
+            Span::styled(
+                "░".repeat((BAR_WIDTH - filled) as usize),
+                Style::default().fg(theme.muted),
+            ),
+        ]),
+    ]
+}
+
+/// A hub's downstream ports: what's plugged in, whether they're powered, and
+/// whether the hub can switch them one by one, e.g. for power-cycling fixtures
+fn port_lines(device: &UsbDevice, app: &App, theme: &Theme) -> Vec<Line<'static>> {
+    if device.class_code != Some(0x09) {
+        return vec![];
+    }
+    let Some(dir) = &device.sysfs_dir else {
+        return vec![];
+    };
+    let ports = hub_ports(dir);
+    if ports.is_empty() {
+        return vec![];
+    }
+
+    let switching = app.hub_switching.get(&device.key()).copied().flatten();
+    let mut lines = vec![Line::from(vec![
+        Span::styled("Ports    ", Style::default().fg(theme.muted)),
+        Span::raw(format!("{}", ports.len())),
+        Span::styled(
+            match switching {
+                Some(PowerSwitching::PerPort) => "  per-port power switching",
+                Some(PowerSwitching::Ganged) => "  ganged power switching",
+                Some(PowerSwitching::None) => "  no power switching",
+                None => "",
+            },
+            Style::default().fg(theme.muted),
+        ),
+    ])];
+    for port in ports {
+        let (marker, color) = match (port.powered, &port.device) {
+            (Some(false), _) => ("○ off", theme.bad),
+            (_, Some(_)) => ("● on ", theme.good),
+            (Some(true), None) => ("· on ", theme.muted),
+            (None, None) => ("· ?  ", theme.muted),
+        };
+        let mut spans = vec![
+            Span::styled(
+                format!("  {:>2} ", port.number),
+                Style::default().fg(theme.muted),
+            ),
+            Span::styled(marker, Style::default().fg(color)),
+        ];
+        match &port.device {
+            Some(path) => {
+                let name = app
+                    .devices
+                    .iter()
+                    .find(|d| d.port_path_string() == *path)
+                    .map_or("", |d| d.name.as_str());
+                spans.push(Span::raw(format!(" {} {}", path, name)));
+            }
+            None => spans.push(Span::styled(" empty", Style::default().fg(theme.muted))),
+        }
+        if let Some(kind) = port
+            .connect_type
+            .filter(|kind| kind != "hotplug" && kind != "unknown")
+        {
+            spans.push(Span::styled(
+                format!(" ({})", kind),
+                Style::default().fg(theme.muted),
+            ));
+        }
+        if switching == Some(PowerSwitching::PerPort) {
+            spans.push(Span::styled(" ⏻", Style::default().fg(theme.accent)));
+        }
+        if let Some(count) = port.over_current_count.filter(|&count| count > 0) {
+            spans.push(Span::styled(
+                format!(" {} over-current", count),
+                Style::default().fg(theme.warn),
+            ));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines
 }
 
-fn render_stats(frame: &mut Frame, area: Rect, app: &App) {
+fn render_stats(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
     let stats = &app.stats;
 
     let refresh_ms = stats.last_refresh_duration.as_micros() as f64 / 1000.0;
+    let e2e_ms = stats.end_to_end_latency.as_micros() as f64 / 1000.0;
+    let latency_style = |ms: f64| {
+        if ms < 10.0 {
+            Style::default().fg(theme.good)
+        } else if ms < 50.0 {
+            Style::default().fg(theme.warn)
+        } else {
+            Style::default().fg(theme.bad)
+        }
+    };
     let rate = stats.refresh_rate();
 
     let lines = vec![
         Line::from(Span::styled(
             "─── Stats ───",
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(theme.muted),
         )),
         Line::from(vec![
-            Span::styled("Refreshes    ", Style::default().fg(Color::DarkGray)),
+            Span::styled("Refreshes    ", Style::default().fg(theme.muted)),
             Span::styled(
                 format!("{}", stats.refresh_count),
-                Style::default().fg(Color::Green),
+                Style::default().fg(theme.good),
             ),
             Span::styled(
-                format!(" ({:.1}/s)", rate),
-                Style::default().fg(Color::DarkGray),
+                match stats.triggers.coalesced {
+                    0 => format!(" ({:.1}/s)", rate),
+                    n => format!(" ({:.1}/s, {} triggers coalesced)", rate, n),
+                },
+                Style::default().fg(theme.muted),
             ),
         ]),
         Line::from(vec![
-            Span::styled("Latency      ", Style::default().fg(Color::DarkGray)),
-            Span::styled(
-                format!("{:.2}ms", refresh_ms),
-                if refresh_ms < 10.0 {
-                    Style::default().fg(Color::Green)
-                } else if refresh_ms < 50.0 {
-                    Style::default().fg(Color::Yellow)
-                } else {
-                    Style::default().fg(Color::Red)
-                },
-            ),
+            Span::styled("Latency      ", Style::default().fg(theme.muted)),
+            Span::styled("scan ", Style::default().fg(theme.muted)),
+            Span::styled(format!("{:.2}ms", refresh_ms), latency_style(refresh_ms)),
+            Span::styled(" / e2e ", Style::default().fg(theme.muted)),
+            Span::styled(format!("{:.2}ms", e2e_ms), latency_style(e2e_ms)),
+        ]),
+        Line::from(vec![
+            Span::styled("Interval     ", Style::default().fg(theme.muted)),
+            Span::raw(format!("{}ms", stats.effective_interval.as_millis())),
+            if stats.effective_interval > app.poll_interval {
+                Span::styled(
+                    format!(" idle, {}ms on a change", app.poll_interval.as_millis()),
+                    Style::default().fg(theme.muted),
+                )
+            } else {
+                Span::raw("")
+            },
+        ]),
+        Line::from(vec![
+            Span::styled("Backend      ", Style::default().fg(theme.muted)),
+            Span::raw(app.backend.name()),
+            if stats.parse_failures > 0 {
+                Span::styled(
+                    format!(" ({} unparsed)", stats.parse_failures),
+                    Style::default().fg(theme.warn),
+                )
+            } else {
+                Span::raw("")
+            },
         ]),
         Line::from(vec![
-            Span::styled("Peak         ", Style::default().fg(Color::DarkGray)),
+            Span::styled("Peak         ", Style::default().fg(theme.muted)),
             Span::raw(format!("{} devices", stats.peak_devices)),
         ]),
         Line::from(vec![
-            Span::styled("Ever seen    ", Style::default().fg(Color::DarkGray)),
+            Span::styled("Ever seen    ", Style::default().fg(theme.muted)),
             Span::raw(format!("{} unique", stats.devices_ever_seen.len())),
         ]),
         Line::from(vec![
-            Span::styled("DFU seen     ", Style::default().fg(Color::DarkGray)),
+            Span::styled("DFU seen     ", Style::default().fg(theme.muted)),
             if stats.dfu_devices_ever_seen.is_empty() {
-                Span::styled("none", Style::default().fg(Color::DarkGray))
+                Span::styled("none", Style::default().fg(theme.muted))
             } else {
                 Span::styled(
                     format!("{}", stats.dfu_devices_ever_seen.len()),
-                    Style::default().fg(Color::Magenta).bold(),
+                    Style::default().fg(theme.dfu).bold(),
                 )
             },
         ]),
         Line::from(vec![
-            Span::styled("Connects     ", Style::default().fg(Color::DarkGray)),
+            Span::styled("Connects     ", Style::default().fg(theme.muted)),
             Span::styled(
                 format!("+{}", stats.connects),
-                Style::default().fg(Color::Green),
+                Style::default().fg(theme.good),
             ),
             Span::raw(" / "),
             Span::styled(
                 format!("-{}", stats.disconnects),
-                Style::default().fg(Color::Red),
+                Style::default().fg(theme.bad),
             ),
         ]),
+        Line::from(vec![
+            Span::styled("Lifetime     ", Style::default().fg(theme.muted)),
+            Span::raw(match stats.average_lifetime() {
+                Some(average) => format!("avg {} over {}", format_hms(average), stats.lifetimes),
+                None => "—".to_string(),
+            }),
+        ]),
+        Line::from(vec![
+            Span::styled("Flakiest     ", Style::default().fg(theme.muted)),
+            match stats.most_reconnected() {
+                Some((name, reconnects)) => Span::styled(
+                    format!("{} ×{}", name, reconnects),
+                    Style::default().fg(theme.warn),
+                ),
+                None => Span::raw("—"),
+            },
+        ]),
+        Line::from(vec![
+            Span::styled("Last change  ", Style::default().fg(theme.muted)),
+            Span::raw(match stats.last_change {
+                Some(at) => format!("{} ago", format_hms(at.elapsed())),
+                None => "none yet".to_string(),
+            }),
+        ]),
     ];
 
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(lines.len() as u16),
+            Constraint::Length(1), // Sparkline title
+            Constraint::Min(0),    // Sparkline
+        ])
+        .split(area);
+
     let stats_widget = Paragraph::new(lines);
-    frame.render_widget(stats_widget, area);
+    frame.render_widget(stats_widget, layout[0]);
+
+    frame.render_widget(
+        Paragraph::new(Span::styled(
+            format!(
+                "─── Devices, last {} ───",
+                format_window(HISTORY_WINDOW.as_secs())
+            ),
+            Style::default().fg(theme.muted),
+        )),
+        layout[1],
+    );
+
+    // Give each column an equal slice of the window, from the oldest sample
+    // on. A column shows the lowest count in its slice so a brief hub dropout
+    // isn't averaged away, or the previous count when a backed-off poll left
+    // it without a sample.
+    let width = (layout[2].width as usize).max(1);
+    let history = &stats.count_history;
+    let slice = HISTORY_WINDOW / width as u32;
+    let mut columns: Vec<Option<usize>> = vec![];
+    if let Some(&(oldest, _)) = history.front() {
+        for &(at, count) in history {
+            let column = ((at.duration_since(oldest).as_secs_f64() / slice.as_secs_f64()) as usize)
+                .min(width - 1);
+            columns.resize(columns.len().max(column + 1), None);
+            let slot = &mut columns[column];
+            *slot = Some(slot.map_or(count, |lowest| lowest.min(count)));
+        }
+    }
+    let mut previous = 0;
+    let data: Vec<u64> = columns
+        .into_iter()
+        .map(|column| {
+            previous = column.unwrap_or(previous);
+            previous as u64
+        })
+        .collect();
+    let sparkline = Sparkline::default()
+        .data(&data)
+        .max(stats.peak_devices.max(1) as u64)
+        .style(Style::default().fg(theme.accent));
+    frame.render_widget(sparkline, layout[2]);
 }
 
-fn render_footer(frame: &mut Frame, area: Rect, app: &App) {
-    let refresh_indicator = if app.stats.refresh_count % 2 == 0 {
-        "●"
+/// "2m" / "45s" for the sparkline title
+fn format_window(secs: u64) -> String {
+    if secs >= 60 && secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
     } else {
-        "○"
-    };
-
-    let footer = Paragraph::new(Line::from(vec![
-        Span::styled(refresh_indicator, Style::default().fg(Color::Green)),
-        Span::raw(" "),
-        Span::styled("↑/↓", Style::default().fg(Color::Cyan)),
-        Span::raw(" navigate  "),
-        Span::styled("r", Style::default().fg(Color::Cyan)),
-        Span::raw(" refresh  "),
-        Span::styled("q", Style::default().fg(Color::Cyan)),
-        Span::raw(" quit"),
-    ]))
-    .block(
-        Block::default()
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::DarkGray)),
-    )
-    .style(Style::default().fg(Color::DarkGray));
+        format!("{}s", secs)
+    }
+}
+
+fn render_log(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let block = Block::default()
+        .title(format!(" Events ({}) ", app.log.len()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border));
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    // Show the newest entries that fit, oldest first
+    let visible = inner.height as usize;
+    let skip = app.log.len().saturating_sub(visible);
+    let lines: Vec<Line> = app
+        .log
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .map(|(index, entry)| {
+            let (marker, color) = match entry.kind {
+                LogKind::Connect => ("+", theme.good),
+                LogKind::Disconnect => ("−", theme.bad),
+            };
+            let delta = match index.checked_sub(1).and_then(|prev| app.log.get(prev)) {
+                Some(prev) => format_delta(entry.at.duration_since(prev.at)),
+                None => String::new(),
+            };
+            Line::from(vec![
+                Span::styled(
+                    entry.timestamp.format("%H:%M:%S ").to_string(),
+                    Style::default().fg(theme.muted),
+                ),
+                Span::styled(format!("{:>8} ", delta), Style::default().fg(theme.border)),
+                Span::styled(marker, Style::default().fg(color).bold()),
+                Span::raw(" "),
+                Span::raw(&entry.name),
+                Span::styled(format!("  {}", entry.key), Style::default().fg(theme.muted)),
+            ])
+        })
+        .collect();
+
+    if lines.is_empty() {
+        let empty = Paragraph::new("No connects or disconnects yet")
+            .style(Style::default().fg(theme.muted));
+        frame.render_widget(empty, inner);
+    } else {
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+}
+
+/// Gap between two events: "+0.42s", or "+3m07s" once it passes a minute
+fn format_delta(delta: Duration) -> String {
+    let secs = delta.as_secs();
+    if secs < 60 {
+        format!("+{:.2}s", delta.as_secs_f64())
+    } else {
+        format!("+{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Tail of the kernel's USB messages, lines about the selected device highlighted
+fn render_kernel_log(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let block = Block::default()
+        .title(" Kernel (usb) ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let Some(log) = &app.kernel_log else {
+        return;
+    };
+    if log.lines.is_empty() {
+        let (text, color) = match &log.unavailable {
+            Some(reason) => (reason.as_str(), theme.warn),
+            None => ("No USB kernel messages yet", theme.muted),
+        };
+        let empty = Paragraph::new(text)
+            .style(Style::default().fg(color))
+            .wrap(Wrap { trim: true });
+        frame.render_widget(empty, inner);
+        return;
+    }
+
+    let selected = app.selected_device();
+    // A tail that stopped keeps its last line for saying so
+    let visible = (inner.height as usize).saturating_sub(usize::from(log.unavailable.is_some()));
+    let mut lines: Vec<Line> = log
+        .lines
+        .iter()
+        .skip(log.lines.len().saturating_sub(visible))
+        .map(|line| {
+            if selected.is_some_and(|device| mentions_device(line, device)) {
+                Line::styled(line.as_str(), Style::default().fg(theme.accent).bold())
+            } else {
+                Line::raw(line.as_str())
+            }
+        })
+        .collect();
+    if let Some(reason) = &log.unavailable {
+        lines.push(Line::styled(
+            reason.as_str(),
+            Style::default().fg(theme.warn),
+        ));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Per-vendor device counts, e.g. "3 STMicroelectronics", largest first
+fn render_vendors(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let summary = app.vendor_summary();
+    let block = Block::default()
+        .title(format!(" Vendors ({}) ", summary.len()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    if summary.is_empty() {
+        let empty = Paragraph::new("No devices").style(Style::default().fg(theme.muted));
+        frame.render_widget(empty, inner);
+        return;
+    }
+    let lines: Vec<Line> = summary
+        .iter()
+        .map(|(name, vendor_id, count)| {
+            let color = if app.vendor_colors {
+                vendor_color(vendor_id, theme)
+            } else {
+                theme.text
+            };
+            let mut spans = vec![
+                Span::styled(format!("{:>3} ", count), Style::default().bold()),
+                Span::styled(name.as_str(), Style::default().fg(color)),
+            ];
+            if name != vendor_id {
+                spans.push(Span::styled(
+                    format!(" {}", vendor_id),
+                    Style::default().fg(theme.muted),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Bounds and step for the device list share of the split layout
+const SPLIT_MIN: u16 = 30;
+const SPLIT_MAX: u16 = 80;
+const SPLIT_STEP: i16 = 5;
+
+/// Footer hint order, after navigation
+const FOOTER_HINTS: [Action; 18] = [
+    Action::Help,
+    Action::Filter,
+    Action::Flash,
+    Action::ToggleLog,
+    Action::KernelLog,
+    Action::Export,
+    Action::Copy,
+    Action::Sort,
+    Action::Layout,
+    Action::Tree,
+    Action::VendorColors,
+    Action::DfuOnly,
+    Action::Descriptors,
+    Action::Pause,
+    Action::Reset,
+    Action::Select,
+    Action::Refresh,
+    Action::Quit,
+];
+
+/// The activity spinner for work in flight, one step per few frames (~80ms at
+/// the 16ms draw loop)
+fn spinner_frame(app: &App) -> char {
+    ACTIVITY_FRAMES[(app.frame_count / 5) as usize % ACTIVITY_FRAMES.len()]
+}
+
+fn render_footer(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    // Pulses once per poll; something slower in flight spins instead
+    let refresh_indicator = if app.busy_label().is_some() {
+        spinner_frame(app)
+    } else if app.stats.refresh_count.is_multiple_of(2) {
+        '●'
+    } else {
+        '○'
+    };
+
+    let spans = if app.searching {
+        vec![
+            Span::styled("/", Style::default().fg(theme.accent).bold()),
+            Span::styled(&app.filter_query, Style::default().fg(theme.text)),
+            Span::styled("█", Style::default().fg(theme.accent)),
+            Span::raw("  "),
+            match app.filter_regex {
+                Some(Err(_)) => Span::styled("bad regex  ", Style::default().fg(theme.bad).bold()),
+                Some(Ok(_)) => Span::styled("regex  ", Style::default().fg(theme.muted)),
+                None if app.filter_query.starts_with('@') => {
+                    Span::styled("port path  ", Style::default().fg(theme.muted))
+                }
+                None => Span::raw(""),
+            },
+            Span::styled("enter", Style::default().fg(theme.accent)),
+            Span::raw(" apply  "),
+            Span::styled("esc", Style::default().fg(theme.accent)),
+            Span::raw(" clear"),
+        ]
+    } else {
+        let mut spans = vec![
+            Span::styled(
+                refresh_indicator.to_string(),
+                Style::default().fg(theme.good),
+            ),
+            Span::raw(" "),
+        ];
+        if let Some(label) = app.busy_label() {
+            spans.push(Span::styled(label, Style::default().fg(theme.accent)));
+            spans.push(Span::raw("  "));
+        }
+        // A fresh status message takes the place of the key hints, which
+        // already fill the line
+        if let Some(status) = app.current_status() {
+            spans.push(Span::styled(
+                &status.text,
+                Style::default().fg(status.color),
+            ));
+        } else {
+            // Built from the keymap so remapped keys show up correctly
+            let nav = [Action::Prev, Action::Next]
+                .map(|action| app.keymap.label(action).unwrap_or_else(|| "-".into()));
+            spans.push(Span::styled(
+                nav.join("/"),
+                Style::default().fg(theme.accent),
+            ));
+            spans.push(Span::raw(" navigate"));
+            for action in FOOTER_HINTS {
+                if let Some(label) = app.keymap.label(action) {
+                    spans.push(Span::raw("  "));
+                    spans.push(Span::styled(label, Style::default().fg(theme.accent)));
+                    spans.push(Span::raw(format!(" {}", action.hint())));
+                }
+            }
+        }
+        if !app.filter_query.is_empty() {
+            spans.push(Span::raw("  "));
+            spans.push(Span::styled(
+                format!("filter: {}", app.filter_query),
+                Style::default().fg(theme.warn),
+            ));
+        }
+        spans
+    };
+
+    let footer = Paragraph::new(Line::from(spans))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.muted)),
+        )
+        .style(Style::default().fg(theme.muted));
 
     frame.render_widget(footer, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static RESTORED: AtomicBool = AtomicBool::new(false);
+
+    fn record_restore() {
+        RESTORED.store(true, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn panic_during_render_restores_the_terminal() {
+        install_panic_hook(record_restore);
+        let mut terminal = ratatui::Terminal::new(TestBackend::new(20, 5)).unwrap();
+        let theme = Theme::new(ThemeName::default());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            terminal
+                .draw(|frame| {
+                    render_too_small(frame, frame.area(), &theme);
+                    panic!("render bug");
+                })
+                .map(|_| ())
+        }));
+        let _ = std::panic::take_hook();
+
+        assert!(result.is_err());
+        assert!(RESTORED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn export_round_trips_at_current_version() {
+        let (mut devices, _) = cursed_usb::parse_lsusb_output(
+            "Bus 001 Device 003: ID 0483:df11 STMicroelectronics STM Device in DFU Mode",
+            &Default::default(),
+            &DfuRules::default(),
+        );
+        devices[0].tty_paths = vec!["/dev/ttyACM0".into()];
+        let json = devices_json(&devices).unwrap();
+        let export: Export<UsbDevice> = serde_json::from_str(&json).unwrap();
+        assert_eq!(export.schema_version, SCHEMA_VERSION);
+        assert_eq!(export.devices, devices);
+    }
+
+    #[test]
+    fn csv_field_quotes_separators_quotes_and_line_breaks() {
+        assert_eq!(csv_field("STLINK-V3"), "STLINK-V3");
+        assert_eq!(csv_field("Acme, Inc."), "\"Acme, Inc.\"");
+        assert_eq!(csv_field("5\" drive"), "\"5\"\" drive\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn middle_truncate_counts_terminal_columns() {
+        let (head, tail) = middle_truncate("株式会社ロジクール USB Receiver", 12);
+        let shown = format!("{}…{}", head, tail);
+        assert!(shown.width() <= 12, "{shown:?} is {} wide", shown.width());
+        assert_eq!(head, "株式会");
+        assert_eq!(tail, "iver");
+    }
+}